@@ -93,6 +93,11 @@ impl Atlas {
         }
     }
 
+    /// Forget every glyph, which will be written again when inserted.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+    }
+
     /// Tries to find an empty slot which can fit the specified rectangle.
     /// Returns a mutable reference to the SpriteSlot if one is found, and None otherwise.
     fn find_empty_slot(&mut self, size: RectSize) -> Option<&mut SpriteSlot> {
@@ -0,0 +1,95 @@
+//! Access to the system clipboard, and to the X11 primary selection.
+
+use std::fmt::Display;
+
+use copypasta::{ClipboardContext, ClipboardProvider};
+#[cfg(all(unix, not(target_os = "macos")))]
+use copypasta::x11_clipboard::{Primary, X11ClipboardContext};
+
+use crate::control::control_type::ClipboardTarget;
+
+pub struct Clipboard {
+    // None if the clipboard couldn't be opened, in which case nothing is copied or pasted.
+    clipboard: Option<ClipboardContext>,
+    #[cfg(all(unix, not(target_os = "macos")))]
+    primary: Option<X11ClipboardContext<Primary>>
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self {
+            clipboard: open("clipboard", ClipboardContext::new()),
+            #[cfg(all(unix, not(target_os = "macos")))]
+            primary: open("primary selection", X11ClipboardContext::<Primary>::new())
+        }
+    }
+    
+    pub fn get_contents(&mut self) -> Option<String> {
+        get_contents("clipboard", self.clipboard.as_mut())
+    }
+    
+    pub fn set_contents(&mut self, contents: String) {
+        set_contents("clipboard", self.clipboard.as_mut(), contents);
+    }
+    
+    pub fn get_target_contents(&mut self, target: ClipboardTarget) -> Option<String> {
+        match target {
+            ClipboardTarget::Clipboard => self.get_contents(),
+            ClipboardTarget::Primary => self.get_primary_contents()
+        }
+    }
+    
+    pub fn set_target_contents(&mut self, target: ClipboardTarget, contents: String) {
+        match target {
+            ClipboardTarget::Clipboard => self.set_contents(contents),
+            ClipboardTarget::Primary => self.set_primary_contents(contents)
+        }
+    }
+    
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn get_primary_contents(&mut self) -> Option<String> {
+        get_contents("primary selection", self.primary.as_mut())
+    }
+    
+    #[cfg(all(unix, not(target_os = "macos")))]
+    pub fn set_primary_contents(&mut self, contents: String) {
+        set_contents("primary selection", self.primary.as_mut(), contents);
+    }
+    
+    // Without a primary selection, the clipboard is used instead.
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    pub fn get_primary_contents(&mut self) -> Option<String> {
+        self.get_contents()
+    }
+    
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    pub fn set_primary_contents(&mut self, _contents: String) {}
+}
+
+fn open<C, E: Display>(name: &str, result: Result<C, E>) -> Option<C> {
+    match result {
+        Ok(clipboard) => Some(clipboard),
+        Err(error) => {
+            println!("Couldn't open the {}: {}", name, error);
+            None
+        }
+    }
+}
+
+fn get_contents(name: &str, clipboard: Option<&mut impl ClipboardProvider>) -> Option<String> {
+    match clipboard?.get_contents() {
+        Ok(contents) => Some(contents),
+        Err(error) => {
+            println!("Couldn't read the {}: {}", name, error);
+            None
+        }
+    }
+}
+
+fn set_contents(name: &str, clipboard: Option<&mut impl ClipboardProvider>, contents: String) {
+    if let Some(clipboard) = clipboard {
+        if let Err(error) = clipboard.set_contents(contents) {
+            println!("Couldn't write to the {}: {}", name, error);
+        }
+    }
+}
@@ -9,13 +9,29 @@ pub struct Config {
     pub env: Option<HashMap<String, String>>,
     pub font: FontConfig,
     pub shell: ShellConfig,
-    pub transparent: bool
+    #[serde(default)]
+    pub scrollback: ScrollbackConfig,
+    pub transparent: bool,
+    #[serde(default)]
+    pub keybindings: Vec<KeyBinding>,
+    #[serde(default)]
+    pub clipboard_access: ClipboardAccess,
+    #[serde(default)]
+    pub hyperlinks: HyperlinkConfig
 }
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct FontConfig {
     pub size: i16,
-    pub path: String
+    pub path: String,
+    /// Fonts used for bold and italic text. When missing, the regular font is emboldened or
+    /// slanted instead.
+    pub bold_path: Option<String>,
+    pub italic_path: Option<String>,
+    pub bold_italic_path: Option<String>,
+    /// Fonts used, in order, for the characters missing from the fonts above.
+    #[serde(default)]
+    pub fallback_paths: Vec<String>
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -23,3 +39,101 @@ pub struct ShellConfig {
     pub program: String,
     pub args: Vec<String>
 }
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ScrollbackConfig {
+    /// Maximum number of lines kept in the history.
+    pub lines: usize,
+    /// Whether new output brings the view back to the bottom while scrolled up in the history.
+    /// If false, the view holds its position.
+    pub scroll_on_output: bool
+}
+
+impl Default for ScrollbackConfig {
+    fn default() -> Self {
+        Self {
+            lines: 10000,
+            scroll_on_output: true
+        }
+    }
+}
+
+/// Command opening the links clicked with Ctrl, given their URI after its arguments.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct HyperlinkConfig {
+    pub program: String,
+    pub args: Vec<String>
+}
+
+impl Default for HyperlinkConfig {
+    fn default() -> Self {
+        Self {
+            program: String::from("xdg-open"),
+            args: vec![]
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct KeyBinding {
+    /// Name of the key, as in glutin's VirtualKeyCode: "C", "Key1", "F1", "PageUp"...
+    pub key: String,
+    /// Modifiers held with the key. The other ones must be released.
+    #[serde(default)]
+    pub mods: Vec<KeyModifier>,
+    pub action: KeyAction
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum KeyModifier {
+    Shift,
+    Ctrl,
+    Alt,
+    Logo
+}
+
+/// What a bound key does instead of being sent to the terminal.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum KeyAction {
+    Copy,
+    Paste,
+    NewTab,
+    NextTab,
+    PreviousTab,
+    ScrollUp,
+    ScrollDown,
+    ScrollPageUp,
+    ScrollPageDown,
+    FontSizeUp,
+    FontSizeDown,
+    /// Send a string to the terminal, as if it was typed
+    SendString(String)
+}
+
+/// What programs may do with the clipboard and the primary selection, through OSC 52.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ClipboardAccess {
+    None,
+    Read,
+    Write,
+    ReadWrite
+}
+
+impl ClipboardAccess {
+    pub fn can_read(self) -> bool {
+        self == ClipboardAccess::Read || self == ClipboardAccess::ReadWrite
+    }
+    
+    pub fn can_write(self) -> bool {
+        self == ClipboardAccess::Write || self == ClipboardAccess::ReadWrite
+    }
+}
+
+// Reading is off by default, as any program, even over ssh, could then read the clipboard.
+impl Default for ClipboardAccess {
+    fn default() -> Self {
+        ClipboardAccess::Write
+    }
+}
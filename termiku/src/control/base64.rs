@@ -0,0 +1,88 @@
+//! Base64, in which OSC 52 carries the contents of the clipboard.
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// '=', filling the last group of 4 characters
+const PADDING: u8 = 0x3D;
+
+pub fn encode(data: &[u8]) -> String {
+    let mut text = String::with_capacity((data.len() + 2) / 3 * 4);
+    
+    for chunk in data.chunks(3) {
+        let bits = (chunk[0] as u32) << 16
+            | (chunk.get(1).copied().unwrap_or(0) as u32) << 8
+            | chunk.get(2).copied().unwrap_or(0) as u32;
+        
+        // 3 bytes make 4 characters, and a chunk of n bytes n + 1 of them.
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(ALPHABET[(bits >> (18 - 6 * index) & 0x3F) as usize] as char);
+            } else {
+                text.push(PADDING as char);
+            }
+        }
+    }
+    
+    text
+}
+
+// The padding is optional, None is returned for any other invalid text.
+pub fn decode(text: &[u8]) -> Option<Vec<u8>> {
+    let length = text.iter().rposition(|&byte| byte != PADDING).map_or(0, |index| index + 1);
+    let text = &text[..length];
+    
+    // A single character left over can't make a byte.
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    
+    let mut data = Vec::with_capacity(text.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    
+    for &byte in text {
+        let value = ALPHABET.iter().position(|&character| character == byte)?;
+        
+        bits = bits << 6 | value as u32;
+        bit_count += 6;
+        
+        if bit_count >= 8 {
+            bit_count -= 8;
+            data.push((bits >> bit_count) as u8);
+        }
+    }
+    
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn decodes_padded_text() {
+        assert_eq!(decode(b"aGVsbG8=").as_deref(), Some(&b"hello"[..]));
+        assert_eq!(decode(b"aGk=").as_deref(), Some(&b"hi"[..]));
+        assert_eq!(decode(b"aGV5").as_deref(), Some(&b"hey"[..]));
+    }
+    
+    #[test]
+    fn decodes_unpadded_text() {
+        assert_eq!(decode(b"aGVsbG8").as_deref(), Some(&b"hello"[..]));
+        assert_eq!(decode(b"aGk").as_deref(), Some(&b"hi"[..]));
+        assert_eq!(decode(b"").as_deref(), Some(&b""[..]));
+    }
+    
+    #[test]
+    fn rejects_invalid_text() {
+        assert_eq!(decode(b"aGVsb"), None);
+        assert_eq!(decode(b"aG*s"), None);
+    }
+    
+    #[test]
+    fn encodes_with_padding() {
+        assert_eq!(encode(b"hello"), "aGVsbG8=");
+        assert_eq!(encode(b"hi"), "aGk=");
+        assert_eq!(encode(b"hey"), "aGV5");
+    }
+}
@@ -12,6 +12,14 @@
 pub enum ControlType {
     Unknown,
 
+    /// ICH\
+    /// CSI Pn 04/00\
+    /// CSI Pn 0x40\
+    /// CSI Pn @\
+    /// \
+    /// Pn = 0x01\
+    InsertCharacter(u16),
+
     /// CUU\
     /// CSI Pn 04/01\
     /// CSI Pn 0x41\
@@ -86,6 +94,22 @@ pub enum ControlType {
     /// Pn = 0x00\
     EraseInPage(u16),
 
+    /// EL\
+    /// CSI Ps 04/11\
+    /// CSI Ps 0x4B\
+    /// CSI Ps K\
+    /// \
+    /// Ps = 0x00\
+    EraseInLine(u16),
+
+    /// IL\
+    /// CSI Pn 04/12\
+    /// CSI Pn 0x4C\
+    /// CSI Pn L\
+    /// \
+    /// Pn = 0x01\
+    InsertLine(u16),
+
     /// DL\
     /// CSI Pn 04/13\
     /// CSI Pn 0x4D\
@@ -94,17 +118,82 @@ pub enum ControlType {
     /// Pn = 0x01\
     DeleteLine(u16),
 
+    /// DCH\
+    /// CSI Pn 05/00\
+    /// CSI Pn 0x50\
+    /// CSI Pn P\
+    /// \
+    /// Pn = 0x01\
+    DeleteCharacter(u16),
+
+    /// SU\
+    /// CSI Pn 05/03\
+    /// CSI Pn 0x53\
+    /// CSI Pn S\
+    /// \
+    /// Pn = 0x01\
+    ScrollUp(u16),
+
+    /// SD\
+    /// CSI Pn 05/04\
+    /// CSI Pn 0x54\
+    /// CSI Pn T\
+    /// \
+    /// Pn = 0x01\
+    ScrollDown(u16),
+
+    /// ECH\
+    /// CSI Pn 05/08\
+    /// CSI Pn 0x58\
+    /// CSI Pn X\
+    /// \
+    /// Pn = 0x01\
+    EraseCharacter(u16),
+
+    /// DECSTBM\
+    /// CSI Pn1;Pn2 07/02\
+    /// CSI Pn1;Pn2 0x72\
+    /// CSI Pn1;Pn2 r\
+    /// \
+    /// Pn1 = 0x01\
+    /// Pn2 = 0x00 (last line)\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by DEC\
+    SetTopAndBottomMargins(u16, u16),
+
+    /// DA\
+    /// CSI Ps 06/03\
+    /// CSI Ps 0x63\
+    /// CSI Ps c\
+    /// \
+    /// Ps = 0x00\
+    DeviceAttributes(u16),
+
+    /// DA2\
+    /// CSI > Ps 06/03\
+    /// CSI > Ps 0x63\
+    /// CSI > Ps c\
+    /// \
+    /// Ps = 0x00\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by DEC\
+    SecondaryDeviceAttributes(u16),
+
     /// SM\
     /// CSI Ps... 06/08\
     /// CSI Ps... 0x68\
     /// CSI Ps... h\
-    SetMode(Vec<u16>),
+    /// \
+    /// Note: DEC private modes are set with CSI ? Ps... h\
+    SetMode(ModeType, Vec<u16>),
 
     /// RM\
     /// CSI Ps... 06/12\
     /// CSI Ps... 0x6C\
     /// CSI Ps... l\
-    ResetMode(Vec<u16>),
+    /// \
+    /// Note: DEC private modes are reset with CSI ? Ps... l\
+    ResetMode(ModeType, Vec<u16>),
 
     /// SGR\
     /// CSI Ps... 06/13\
@@ -114,6 +203,45 @@ pub enum ControlType {
     /// Ps = 0\
     SelectGraphicRendition(Vec<u16>),
 
+    /// DSR\
+    /// CSI Ps 06/14\
+    /// CSI Ps 0x6E\
+    /// CSI Ps n\
+    /// \
+    /// Ps = 0x00\
+    DeviceStatusReport(u16),
+
+    /// DECRQM\
+    /// CSI Ps 02/04 07/00\
+    /// CSI Ps 0x24 0x70\
+    /// CSI Ps $ p\
+    /// \
+    /// Ps = 0x00\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by DEC. DEC private modes are requested with\
+    /// CSI ? Ps $ p\
+    RequestMode(ModeType, u16),
+
+    /// XTVERSION\
+    /// CSI > Ps 07/01\
+    /// CSI > Ps 0x71\
+    /// CSI > Ps q\
+    /// \
+    /// Ps = 0x00\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by xterm\
+    ReportVersion,
+
+    /// DECSCUSR\
+    /// CSI Ps 02/00 07/01\
+    /// CSI Ps 0x20 0x71\
+    /// CSI Ps SP q\
+    /// \
+    /// Ps = 0x00\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by DEC\
+    SetCursorStyle(u16),
+
     /// ???\
     /// CSI 0x73\
     /// CSI s\
@@ -121,6 +249,14 @@ pub enum ControlType {
     /// Note: Not documented by ECMA-48\
     SaveCursor,
 
+    /// XTWINOPS\
+    /// CSI Ps... 07/04\
+    /// CSI Ps... 0x74\
+    /// CSI Ps... t\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by xterm\
+    WindowManipulation(Vec<u16>),
+
     /// ???\
     /// CSI 0x75\
     /// CSI u\
@@ -128,6 +264,138 @@ pub enum ControlType {
     /// Note: Not documented by ECMA-48\
     RestoreCursor,
 
+    // Escape sequences, made of ESC, intermediate bytes and a final byte
+
+    /// DECSC\
+    /// ESC 03/07\
+    /// ESC 0x37\
+    /// ESC 7\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by DEC\
+    SaveCursorAndAttributes,
+
+    /// DECRC\
+    /// ESC 03/08\
+    /// ESC 0x38\
+    /// ESC 8\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by DEC\
+    RestoreCursorAndAttributes,
+
+    /// DECKPAM\
+    /// ESC 03/13\
+    /// ESC 0x3D\
+    /// ESC =\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by DEC\
+    KeypadApplicationMode,
+
+    /// DECKPNM\
+    /// ESC 03/14\
+    /// ESC 0x3E\
+    /// ESC >\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by DEC\
+    KeypadNumericMode,
+
+    /// IND\
+    /// ESC 04/04\
+    /// ESC 0x44\
+    /// ESC D\
+    /// \
+    /// Note: Removed from ECMA-48, defined by DEC\
+    Index,
+
+    /// NEL\
+    /// ESC 04/05\
+    /// ESC 0x45\
+    /// ESC E\
+    NextLine,
+
+    /// RI\
+    /// ESC 04/13\
+    /// ESC 0x4D\
+    /// ESC M\
+    ReverseLineFeed,
+
+    /// SS2\
+    /// ESC 04/14\
+    /// ESC 0x4E\
+    /// ESC N\
+    SingleShiftTwo,
+
+    /// SS3\
+    /// ESC 04/15\
+    /// ESC 0x4F\
+    /// ESC O\
+    SingleShiftThree,
+
+    /// RIS\
+    /// ESC 06/03\
+    /// ESC 0x63\
+    /// ESC c\
+    ResetToInitialState,
+
+    /// SCS\
+    /// ESC I F\
+    /// ESC 0x28..0x2B F\
+    /// ESC ( F, ESC ) F, ESC * F, ESC + F\
+    /// \
+    /// I selects G0 to G3, F the character set\
+    /// \
+    /// Note: Not documented by ECMA-48, defined by ECMA-35\
+    DesignateCharacterSet(usize, CharacterSet),
+
+    // Operating system commands, made of OSC, a command string and ST or BEL.
+    // Their numbers are not documented by ECMA-48, but defined by xterm.
+
+    /// OSC 0\
+    /// OSC 0 ; Pt ST\
+    /// ESC 0x5D 0x30 0x3B Pt ESC 0x5C\
+    /// ESC ] 0 ; Pt ESC \\
+    SetIconNameAndWindowTitle(String),
+
+    /// OSC 1\
+    /// OSC 1 ; Pt ST\
+    /// ESC 0x5D 0x31 0x3B Pt ESC 0x5C\
+    /// ESC ] 1 ; Pt ESC \\
+    SetIconName(String),
+
+    /// OSC 2\
+    /// OSC 2 ; Pt ST\
+    /// ESC 0x5D 0x32 0x3B Pt ESC 0x5C\
+    /// ESC ] 2 ; Pt ESC \\
+    SetWindowTitle(String),
+
+    /// OSC 8\
+    /// OSC 8 ; Pp ; Pu ST\
+    /// ESC 0x5D 0x38 0x3B Pp 0x3B Pu ESC 0x5C\
+    /// ESC ] 8 ; Pp ; Pu ESC \\
+    /// \
+    /// Pp are parameters "key=value" separated by ':', among which only "id" is used, Pu is the
+    /// URI of the hyperlink of the next characters printed\
+    /// \
+    /// Note: An empty Pu ends the hyperlink
+    SetHyperlink(Option<String>, String),
+
+    /// OSC 52\
+    /// OSC 52 ; Pc ; Pd ST\
+    /// ESC 0x5D 0x35 0x32 0x3B Pc 0x3B Pd ESC 0x5C\
+    /// ESC ] 52 ; Pc ; Pd ESC \\
+    /// \
+    /// Pc selects the clipboard or the primary selection, Pd is its new contents in base64\
+    /// \
+    /// Note: Pd being '?' is QueryClipboard instead
+    SetClipboard(ClipboardTarget, String),
+
+    /// OSC 52\
+    /// OSC 52 ; Pc ; ? ST\
+    /// ESC 0x5D 0x35 0x32 0x3B Pc 0x3B 0x3F ESC 0x5C\
+    /// ESC ] 52 ; Pc ; ? ESC \\
+    /// \
+    /// Replied to with SetClipboard, holding the contents of the selected clipboard
+    QueryClipboard(ClipboardTarget),
+
     // Termiku's private control sequnces
     
     /// TYP\
@@ -135,3 +403,31 @@ pub enum ControlType {
     /// CSI y\
     TermikuYoutubePlayback([u8; 11])
 }
+
+/// 94 characters sets which can be designated by SCS
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CharacterSet {
+    /// ESC ( B
+    Ascii,
+    /// ESC ( 0, line drawing characters
+    DecSpecialGraphics,
+    /// ESC ( A, ASCII with '£' instead of '#'
+    Uk,
+}
+
+/// Clipboards which can be accessed by OSC 52
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClipboardTarget {
+    /// 'c'
+    Clipboard,
+    /// 'p', and 's' which xterm lets users configure
+    Primary,
+}
+
+/// Modes set by SM and RM: the ones of ECMA-48, or the private ones defined by DEC, whose
+/// parameters start with '?'
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ModeType {
+    Ansi,
+    DecPrivate,
+}
@@ -1,5 +1,6 @@
 mod recognize;
 
+pub mod base64;
 pub mod control_type;
 
 use std::ops::RangeInclusive;
@@ -13,6 +14,45 @@ pub const CSI_1: u8 = 0x1B;
 /// '['
 const CSI_2: u8 = 0x5B;
 
+/// ']'
+const OSC_2: u8 = 0x5D;
+
+/// 'P', DCS when preceded by ESC
+const DCS_2: u8 = 0x50;
+
+/// 'X', SOS when preceded by ESC
+const SOS_2: u8 = 0x58;
+
+/// '^', PM when preceded by ESC
+const PM_2: u8 = 0x5E;
+
+/// '_', APC when preceded by ESC
+const APC_2: u8 = 0x5F;
+
+/// BEL, terminates a control string, as an alternative to ST
+const BEL: u8 = 0x07;
+
+/// '\\', preceded by ESC, forms ST
+const ST_2: u8 = 0x5C;
+
+/// ST, as a single C1 byte
+const ST_C1: u8 = 0x9C;
+
+/// CAN, cancels a control string
+const CAN: u8 = 0x18;
+
+/// SUB, cancels a control string like CAN
+const SUB: u8 = 0x1A;
+
+/// Maximum length of the payload of a control string, large enough for the clipboard contents set
+/// by OSC 52.
+/// Strings going past it are discarded, as their truncated payload would be wrong.
+const STRING_MAX_LENGTH: usize = 1 << 20;
+
+/// Maximum length of the parameters and URI of OSC 8, a lot shorter than other strings as every
+/// link set is kept by the screen.
+const HYPERLINK_MAX_LENGTH: usize = 4096;
+
 /// '0'
 const PARAMETER_START: u8 = 0x30;
 
@@ -40,9 +80,22 @@ const FINAL_END: u8 = 0x7E;
 /// "@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\]^_`abcdefghijklmnopqrstuvwxyz{|}~"
 const FINAL_RANGE: RangeInclusive<u8> = FINAL_START..=FINAL_END;
 
+/// '0'
+const ESCAPE_FINAL_START: u8 = 0x30;
+
+/// "0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\]^_`abcdefghijklmnopqrstuvwxyz{|}~"
+/// Final bytes of an escape sequence, as defined by ECMA-35 (Section 13.2.2): Fp, Fe and Fs
+/// without intermediate bytes, and F with them.
+const ESCAPE_FINAL_RANGE: RangeInclusive<u8> = ESCAPE_FINAL_START..=FINAL_END;
+
 
 /// A control sequence parser, according to ECMA-48 definition (Section 5.4)
 /// 
+/// Also recognizes escape sequences made of ESC, intermediate bytes and a final byte (ECMA-35,
+/// Section 13.2), and control strings, ended by ST or BEL (ECMA-48, Section 8.3.89), or cancelled
+/// by CAN or SUB. Only operating system commands are interpreted, the payload of the other control
+/// strings (DCS, SOS, PM and APC) is dropped.
+/// 
 /// Parse bytes one by one with `parse_byte`.
 /// `reset` should be called on error.
 #[derive(Debug)]
@@ -51,6 +104,11 @@ pub struct ControlSequenceParser {
     buffer: Vec<u8>,
     parameter_length: usize,
     intermediary_length: usize,
+    // Whether the payload of a control string went past STRING_MAX_LENGTH
+    string_overflowed: bool,
+    // Continuation bytes still expected for the UTF-8 character of the control string being
+    // received, inside which 0x9C isn't ST
+    string_utf8_remaining: usize,
     
     parameters_buffer: Vec<Option<u16>>,
 }
@@ -62,6 +120,12 @@ enum ParserState {
     ParsingParameter,
     ParsingIntermediary,
     // No ParsingFinal, as the final byte is only of length 1.
+    // Intermediate bytes of an escape sequence, after ESC.
+    ParsingEscapeIntermediary,
+    // Payload of a control string, the byte after ESC telling which one it is.
+    ParsingString,
+    // ESC was received in a control string, which is ended if it's followed by '\'.
+    ParsingStringEscape,
 }
 
 #[derive(Debug)]
@@ -82,6 +146,8 @@ impl ControlSequenceParser {
             buffer: Vec::with_capacity(64),
             parameter_length: 0,
             intermediary_length: 0,
+            string_overflowed: false,
+            string_utf8_remaining: 0,
             parameters_buffer: Vec::with_capacity(64)
         }
     }
@@ -116,10 +182,35 @@ impl ControlSequenceParser {
                     self.buffer.push(byte);
                     self.state = ParserState::ParsingParameter;
                     Ok(None)
+                } else if [OSC_2, DCS_2, SOS_2, PM_2, APC_2].contains(&byte) {
+                    self.buffer.push(byte);
+                    self.state = ParserState::ParsingString;
+                    Ok(None)
+                } else if INTERMEDIARY_RANGE.contains(&byte) {
+                    self.buffer.push(byte);
+                    self.intermediary_length += 1;
+                    self.state = ParserState::ParsingEscapeIntermediary;
+                    Ok(None)
+                } else if ESCAPE_FINAL_RANGE.contains(&byte) {
+                    self.flush();
+                    Ok(Some(interpret_escape(&[], byte)))
                 } else {
                     Err(ControlSequenceError::InvalidCsi2Byte)
                 }
             },
+            ParserState::ParsingEscapeIntermediary => {
+                if INTERMEDIARY_RANGE.contains(&byte) {
+                    self.buffer.push(byte);
+                    self.intermediary_length += 1;
+                    Ok(None)
+                } else if ESCAPE_FINAL_RANGE.contains(&byte) {
+                    let control_type = interpret_escape(&self.buffer[1..], byte);
+                    self.flush();
+                    Ok(Some(control_type))
+                } else {
+                    Err(ControlSequenceError::InvalidIntermediaryByte)
+                }
+            },
             ParserState::ParsingParameter => {
                 if PARAMETER_RANGE.contains(&byte) {
                     self.buffer.push(byte);
@@ -150,6 +241,41 @@ impl ControlSequenceParser {
                 } else {
                     Err(ControlSequenceError::InvalidIntermediaryByte)
                 }
+            },
+            ParserState::ParsingString => {
+                if byte == BEL || (byte == ST_C1 && self.string_utf8_remaining == 0) {
+                    Ok(Some(self.parse_string_buffer()))
+                } else if byte == CAN || byte == SUB {
+                    // The string is dropped without being interpreted.
+                    self.flush();
+                    Ok(None)
+                } else if byte == CSI_1 {
+                    self.state = ParserState::ParsingStringEscape;
+                    Ok(None)
+                } else {
+                    self.string_utf8_remaining = get_utf8_remaining(self.string_utf8_remaining, byte);
+                    
+                    // Other control characters have no meaning in the payload.
+                    if byte >= 0x20 && self.buffer.len() < STRING_MAX_LENGTH + 2 {
+                        self.buffer.push(byte);
+                    } else if byte >= 0x20 {
+                        self.string_overflowed = true;
+                    }
+                    Ok(None)
+                }
+            },
+            ParserState::ParsingStringEscape => {
+                if byte == ST_2 {
+                    Ok(Some(self.parse_string_buffer()))
+                } else if byte == CAN || byte == SUB {
+                    self.flush();
+                    Ok(None)
+                } else {
+                    // The string is cancelled by the start of another escape sequence.
+                    self.flush();
+                    self.parse_byte(CSI_1)?;
+                    self.parse_byte(byte)
+                }
             }
         }
     }
@@ -158,6 +284,8 @@ impl ControlSequenceParser {
         self.state = ParserState::NotParsing;
         self.intermediary_length = 0;
         self.parameter_length = 0;
+        self.string_overflowed = false;
+        self.string_utf8_remaining = 0;
         //self.buffer.drain(0..self.buffer.len()).collect()
         let v = self.buffer.clone();
         self.buffer.clear();
@@ -187,4 +315,152 @@ impl ControlSequenceParser {
         control_type        
     }
     
+    // Interpret the payload of a control string, and reset the parser.
+    fn parse_string_buffer(&mut self) -> ControlType {
+        let control_type = if self.string_overflowed {
+            println!("control string longer than {} bytes discarded", STRING_MAX_LENGTH);
+            ControlType::Unknown
+        } else if self.buffer[1] == OSC_2 {
+            interpret_osc(&self.buffer[2..])
+        } else {
+            println!("unsupported control string: {:02X?}", self.buffer[1]);
+            ControlType::Unknown
+        };
+        
+        self.flush();
+        control_type
+    }
+    
+}
+
+// Continuation bytes still expected for a UTF-8 character after `byte`, `remaining` being the
+// number expected before it.
+fn get_utf8_remaining(remaining: usize, byte: u8) -> usize {
+    match byte {
+        0x80..=0xBF => remaining.saturating_sub(1),
+        0xC0..=0xDF => 1,
+        0xE0..=0xEF => 2,
+        0xF0..=0xF7 => 3,
+        _ => 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ControlType::*;
+    
+    // Every byte must be part of a control sequence.
+    fn parse(bytes: &[u8]) -> Vec<ControlType> {
+        let mut parser = ControlSequenceParser::new();
+        
+        bytes.iter()
+            .filter_map(|&byte| parser.parse_byte(byte).unwrap())
+            .collect()
+    }
+    
+    #[test]
+    fn escape_sequences_take_intermediate_bytes() {
+        match &parse(b"\x1B7\x1B(0\x1B)B\x1B8")[..] {
+            [
+                SaveCursorAndAttributes,
+                DesignateCharacterSet(0, CharacterSet::DecSpecialGraphics),
+                DesignateCharacterSet(1, CharacterSet::Ascii),
+                RestoreCursorAndAttributes
+            ] => {},
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn commands_end_with_bel_or_st() {
+        match &parse(b"\x1B]2;with bel\x07\x1B]0;with st\x1B\\")[..] {
+            [SetWindowTitle(first), SetIconNameAndWindowTitle(second)] => {
+                assert_eq!(first, "with bel");
+                assert_eq!(second, "with st");
+            },
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn control_characters_are_left_out_of_commands() {
+        match &parse(b"\x1B]2;a\x08b\tc\n\x07")[..] {
+            [SetWindowTitle(title)] => assert_eq!(title, "abc"),
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn an_escape_sequence_cancels_a_command() {
+        match &parse(b"\x1B]2;abc\x1B[5L")[..] {
+            [InsertLine(5)] => {},
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn can_and_sub_cancel_a_command() {
+        match &parse(b"\x1B]2;abc\x18\x1B]2;def\x1B\x1A\x1B]2;ghi\x07")[..] {
+            [SetWindowTitle(title)] => assert_eq!(title, "ghi"),
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn c1_st_ends_a_command_outside_of_utf8_characters() {
+        // 'Ĝ' is encoded as 0xC4 0x9C.
+        match &parse(b"\x1B]2;\xC4\x9C\x07")[..] {
+            [SetWindowTitle(title)] => assert_eq!(title, "Ĝ"),
+            other => panic!("unexpected controls: {:?}", other)
+        }
+        
+        match &parse(b"\x1B]2;abc\x9C")[..] {
+            [SetWindowTitle(title)] => assert_eq!(title, "abc"),
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn other_control_strings_are_dropped() {
+        match &parse(b"\x1BP+q544e\x1B\\\x1BXa\x07\x1B^b\x9C\x1B_c\x18\x1B]2;title\x07")[..] {
+            [Unknown, Unknown, Unknown, SetWindowTitle(title)] => assert_eq!(title, "title"),
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn hyperlinks_too_long_end_the_link() {
+        let mut bytes = b"\x1B]8;;".to_vec();
+        bytes.resize(HYPERLINK_MAX_LENGTH + 8, b'a');
+        bytes.push(BEL);
+        
+        match &parse(&bytes)[..] {
+            [SetHyperlink(None, uri)] => assert!(uri.is_empty()),
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn commands_longer_than_the_bound_are_discarded() {
+        let mut bytes = b"\x1B]2;".to_vec();
+        bytes.resize(STRING_MAX_LENGTH * 2, b'a');
+        bytes.push(BEL);
+        bytes.extend_from_slice(b"\x1B]2;title\x07");
+        
+        match &parse(&bytes)[..] {
+            [Unknown, SetWindowTitle(title)] => assert_eq!(title, "title"),
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
+    
+    #[test]
+    fn clipboard_commands_decode_their_contents() {
+        match &parse(b"\x1B]52;p;aGk=\x07\x1B]52;;?\x07")[..] {
+            [SetClipboard(ClipboardTarget::Primary, contents), QueryClipboard(ClipboardTarget::Clipboard)] => {
+                assert_eq!(contents, "hi");
+            },
+            other => panic!("unexpected controls: {:?}", other)
+        }
+    }
 }
@@ -8,6 +8,18 @@ pub fn interpret_long_control(
     use ControlType::*;
     
     match final_byte {
+        0x40 => {
+            // ICH
+            if intermediary_bytes.is_empty() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let value = get_parameter_default(parameters_buffer, 0, 1);
+                
+                InsertCharacter(value)
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
         0x41 => {
             // CUU
             if intermediary_bytes.is_empty() {
@@ -117,6 +129,30 @@ pub fn interpret_long_control(
                 log_unknown(parameter_bytes, intermediary_bytes, final_byte)
             }
         },
+        0x4B => {
+            // EL
+            if intermediary_bytes.is_empty() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let value = get_parameter_default(parameters_buffer, 0, 0);
+                
+                EraseInLine(value)
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
+        0x4C => {
+            // IL
+            if intermediary_bytes.is_empty() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let value = get_parameter_default(parameters_buffer, 0, 1);
+                
+                InsertLine(value)
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
         0x4D => {
             // DL
             if intermediary_bytes.is_empty() {
@@ -129,28 +165,151 @@ pub fn interpret_long_control(
                 log_unknown(parameter_bytes, intermediary_bytes, final_byte)
             }
         },
-        0x68 => {
-            if intermediary_bytes.is_empty() {                
-                SetMode(parse_unknown_length(parameter_bytes, parameters_buffer))
+        0x50 => {
+            // DCH
+            if intermediary_bytes.is_empty() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let value = get_parameter_default(parameters_buffer, 0, 1);
+                
+                DeleteCharacter(value)
             } else {
                 log_unknown(parameter_bytes, intermediary_bytes, final_byte)
             }
         },
-        0x6C => {
-            if intermediary_bytes.is_empty() {                
-                ResetMode(parse_unknown_length(parameter_bytes, parameters_buffer))
+        0x53 => {
+            // SU
+            if intermediary_bytes.is_empty() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let value = get_parameter_default(parameters_buffer, 0, 1);
+                
+                ScrollUp(value)
             } else {
                 log_unknown(parameter_bytes, intermediary_bytes, final_byte)
             }
-        }        
+        },
+        0x54 => {
+            // SD
+            if intermediary_bytes.is_empty() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let value = get_parameter_default(parameters_buffer, 0, 1);
+                
+                ScrollDown(value)
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
+        0x58 => {
+            // ECH
+            if intermediary_bytes.is_empty() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let value = get_parameter_default(parameters_buffer, 0, 1);
+                
+                EraseCharacter(value)
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
+        0x63 => {
+            // DA
+            if intermediary_bytes.is_empty() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let parameter = get_parameter_default(parameters_buffer, 0, 0);
+                
+                match get_private_prefix(parameter_bytes) {
+                    None => DeviceAttributes(parameter),
+                    Some(b'>') => SecondaryDeviceAttributes(parameter),
+                    Some(_) => log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+                }
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
+        0x68 => {
+            // SM
+            match (intermediary_bytes.is_empty(), get_mode_type(parameter_bytes)) {
+                (true, Some(mode_type)) => SetMode(mode_type, parse_unknown_length(parameter_bytes, parameters_buffer)),
+                _ => log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
+        0x6C => {
+            // RM
+            match (intermediary_bytes.is_empty(), get_mode_type(parameter_bytes)) {
+                (true, Some(mode_type)) => ResetMode(mode_type, parse_unknown_length(parameter_bytes, parameters_buffer)),
+                _ => log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
         0x6D => {
             // SGR
             if intermediary_bytes.is_empty() {
+                if get_private_prefix(parameter_bytes).is_some() {
+                    return log_unknown(parameter_bytes, intermediary_bytes, final_byte);
+                }
+                
                 SelectGraphicRendition(parse_unknown_length(parameter_bytes, parameters_buffer))
             } else {
                 log_unknown(parameter_bytes, intermediary_bytes, final_byte)
             }
         },
+        0x6E => {
+            // DSR
+            if intermediary_bytes.is_empty() && get_private_prefix(parameter_bytes).is_none() {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                DeviceStatusReport(get_parameter_default(parameters_buffer, 0, 0))
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
+        0x70 => {
+            // DECRQM
+            if intermediary_bytes == b"$" {
+                parse_parameters(parameter_bytes, parameters_buffer);
+                
+                let mode = get_parameter_default(parameters_buffer, 0, 0);
+                
+                match get_mode_type(parameter_bytes) {
+                    Some(mode_type) => RequestMode(mode_type, mode),
+                    None => log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+                }
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
+        0x71 => {
+            match (intermediary_bytes, get_private_prefix(parameter_bytes)) {
+                // XTVERSION
+                ([], Some(b'>')) => ReportVersion,
+                // DECSCUSR
+                ([b' '], None) => {
+                    parse_parameters(parameter_bytes, parameters_buffer);
+                    
+                    SetCursorStyle(get_parameter_default(parameters_buffer, 0, 0))
+                },
+                _ => log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
+        0x72 => {
+            // DECSTBM
+            if intermediary_bytes.is_empty() {
+                let private = parse_parameters(parameter_bytes, parameters_buffer);
+                
+                if private {
+                    return log_unknown(parameter_bytes, intermediary_bytes, final_byte);
+                }
+                
+                let top = get_parameter_default(parameters_buffer, 0, 1);
+                let bottom = get_parameter_default(parameters_buffer, 1, 0);
+                
+                SetTopAndBottomMargins(top, bottom)
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
         0x73 => {
             // SaveCursor
             if intermediary_bytes.is_empty() {
@@ -159,6 +318,18 @@ pub fn interpret_long_control(
                 log_unknown(parameter_bytes, intermediary_bytes, final_byte)
             }
         },
+        0x74 => {
+            // XTWINOPS
+            if intermediary_bytes.is_empty() {
+                if get_private_prefix(parameter_bytes).is_some() {
+                    return log_unknown(parameter_bytes, intermediary_bytes, final_byte);
+                }
+                
+                WindowManipulation(parse_unknown_length(parameter_bytes, parameters_buffer))
+            } else {
+                log_unknown(parameter_bytes, intermediary_bytes, final_byte)
+            }
+        },
         0x75 => {
             // RestoreCursor
             if intermediary_bytes.is_empty() {
@@ -200,6 +371,138 @@ pub fn interpret_long_control(
     }
 }
 
+// Interpret an escape sequence made of ESC, intermediate bytes and a final byte.
+pub fn interpret_escape(intermediary_bytes: &[u8], final_byte: u8) -> ControlType {
+    use ControlType::*;
+    
+    match intermediary_bytes {
+        [] => match final_byte {
+            // DECSC
+            0x37 => SaveCursorAndAttributes,
+            // DECRC
+            0x38 => RestoreCursorAndAttributes,
+            // DECKPAM
+            0x3D => KeypadApplicationMode,
+            // DECKPNM
+            0x3E => KeypadNumericMode,
+            // IND
+            0x44 => Index,
+            // NEL
+            0x45 => NextLine,
+            // RI
+            0x4D => ReverseLineFeed,
+            // SS2
+            0x4E => SingleShiftTwo,
+            // SS3
+            0x4F => SingleShiftThree,
+            // RIS
+            0x63 => ResetToInitialState,
+            _ => log_unknown_escape(intermediary_bytes, final_byte)
+        },
+        // SCS, for the 94 characters sets G0 to G3
+        [intermediary @ 0x28..=0x2B] => {
+            let index = (intermediary - 0x28) as usize;
+            
+            match final_byte {
+                0x30 => DesignateCharacterSet(index, CharacterSet::DecSpecialGraphics),
+                0x41 => DesignateCharacterSet(index, CharacterSet::Uk),
+                0x42 => DesignateCharacterSet(index, CharacterSet::Ascii),
+                _ => log_unknown_escape(intermediary_bytes, final_byte)
+            }
+        },
+        _ => log_unknown_escape(intermediary_bytes, final_byte)
+    }
+}
+
+fn log_unknown_escape(intermediary_bytes: &[u8], final_byte: u8) -> ControlType {
+    println!("unknown escape sequence: inter: {:02X?}, final: {:02X?}", intermediary_bytes, final_byte);
+    ControlType::Unknown
+}
+
+// Interpret the command string of an operating system command, made of a number, and of text
+// following a ';'.
+pub fn interpret_osc(command: &[u8]) -> ControlType {
+    use ControlType::*;
+    
+    let (number, text) = match command.iter().position(|&byte| byte == b';') {
+        Some(index) => (&command[..index], &command[index + 1..]),
+        None => (command, &command[command.len()..])
+    };
+    
+    match number {
+        b"0" => SetIconNameAndWindowTitle(String::from_utf8_lossy(text).to_string()),
+        b"1" => SetIconName(String::from_utf8_lossy(text).to_string()),
+        b"2" => SetWindowTitle(String::from_utf8_lossy(text).to_string()),
+        b"8" => interpret_hyperlink(text),
+        b"52" => interpret_clipboard_command(text),
+        _ => {
+            println!("unknown operating system command: {:?}", String::from_utf8_lossy(command));
+            Unknown
+        }
+    }
+}
+
+// OSC 8 text: parameters separated by ':', and the URI of the link after a ';'. Links with the
+// same "id" parameter and URI are the same link, even when they aren't next to each other.
+fn interpret_hyperlink(text: &[u8]) -> ControlType {
+    use ControlType::*;
+    
+    // The link is ended rather than ignored, so that the text it was given for doesn't get the
+    // previous one.
+    if text.len() > HYPERLINK_MAX_LENGTH {
+        println!("hyperlink longer than {} bytes ended", HYPERLINK_MAX_LENGTH);
+        return SetHyperlink(None, String::new());
+    }
+    
+    let (parameters, uri) = match text.iter().position(|&byte| byte == b';') {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => {
+            println!("invalid hyperlink: {:?}", String::from_utf8_lossy(text));
+            return Unknown;
+        }
+    };
+    
+    let id = parameters.split(|&byte| byte == b':')
+        .find(|parameter| parameter.starts_with(b"id="))
+        .map(|parameter| String::from_utf8_lossy(&parameter[3..]).to_string())
+        .filter(|id| !id.is_empty());
+    
+    SetHyperlink(id, String::from_utf8_lossy(uri).to_string())
+}
+
+// OSC 52 text: the clipboards to access, and the new contents in base64 or '?' to query them.
+// Only the first clipboard named is used, and the cut buffers ('0' to '7') aren't supported. When
+// none is named, it's the clipboard.
+fn interpret_clipboard_command(text: &[u8]) -> ControlType {
+    use ControlType::*;
+    
+    let (targets, data) = match text.iter().position(|&byte| byte == b';') {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => {
+            println!("invalid clipboard command of {} bytes", text.len());
+            return Unknown;
+        }
+    };
+    
+    let target = targets.iter().find_map(|byte| match byte {
+        b'c' => Some(ClipboardTarget::Clipboard),
+        b'p' | b's' => Some(ClipboardTarget::Primary),
+        _ => None
+    }).unwrap_or(ClipboardTarget::Clipboard);
+    
+    if data == b"?" {
+        return QueryClipboard(target);
+    }
+    
+    match base64::decode(data) {
+        Some(contents) => SetClipboard(target, String::from_utf8_lossy(&contents).to_string()),
+        None => {
+            println!("invalid base64 in clipboard command of {} bytes", data.len());
+            Unknown
+        }
+    }
+}
+
 fn parse_unknown_length(parameters_bytes: &[u8], parameters_buffer: &mut Vec<Option<u16>>) -> Vec<u16> {
     parse_parameters(parameters_bytes, parameters_buffer);
     
@@ -229,6 +532,22 @@ fn log_unknown(parameter_bytes: &[u8], intermediary_bytes: &[u8], final_byte: u8
 const NUMBER_RANGE: std::ops::RangeInclusive<u8> = 0x30..=0x39;
 const PRIVATE_PARAMETERS_RANGE: std::ops::RangeInclusive<u8> =0x3C..=0x3F;
 
+// Byte starting private parameters, one of "<=>?", if there's one.
+fn get_private_prefix(parameter_bytes: &[u8]) -> Option<u8> {
+    parameter_bytes.first()
+        .copied()
+        .filter(|byte| PRIVATE_PARAMETERS_RANGE.contains(byte))
+}
+
+// Modes are DEC private ones when their parameters start with '?'.
+fn get_mode_type(parameter_bytes: &[u8]) -> Option<ModeType> {
+    match get_private_prefix(parameter_bytes) {
+        None => Some(ModeType::Ansi),
+        Some(b'?') => Some(ModeType::DecPrivate),
+        Some(_) => None
+    }
+}
+
 // Parse the parameters bytes.
 // Not always called to save time for cases when they're not actually required.
 // Somewhat follows ECMA-48 definition (Section 5.4.1 and 5.4.2), but doesn't handle sub-strings,
@@ -7,8 +7,10 @@ use glium::uniforms::Uniforms;
 
 use crate::atlas::*;
 use crate::config::*;
+use crate::freetype::FreeTypeGlyph;
 use crate::pty_buffer::{*, sgr::SimpleColor};
 use crate::rasterizer::*;
+use crate::window::DEFAULT_BG;
 
 #[derive(Copy, Clone, Debug)]
 struct CharVertex {
@@ -27,6 +29,14 @@ struct BgVertex {
 
 implement_vertex!(BgVertex, position, colour);
 
+// How the cursor is drawn: with the shape it was given, or as a hollow block when the window
+// isn't focused.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CursorDrawing {
+    Shape(CursorShape),
+    Hollow,
+}
+
 
 pub struct Drawer<'a> {
     config: Config,
@@ -35,6 +45,8 @@ pub struct Drawer<'a> {
     index_buffer: NoIndices,
     draw_parameters: DrawParameters<'a>,
     pub atlas: Atlas,
+    // Link under the mouse, whose cells are underlined
+    hovered_hyperlink: Option<u32>,
 }
 
 struct ProgramWrapper {
@@ -152,6 +164,7 @@ impl <'a> Drawer<'a> {
             index_buffer,
             draw_parameters,
             atlas,
+            hovered_hyperlink: None,
         }
     }
     
@@ -174,35 +187,180 @@ impl <'a> Drawer<'a> {
     }
     
     
+    // Returns true if the hovered link has changed.
+    pub fn set_hovered_hyperlink(&mut self, hyperlink: Option<u32>) -> bool {
+        let changed = self.hovered_hyperlink != hyperlink;
+        self.hovered_hyperlink = hyperlink;
+        changed
+    }
+    
     // TODO really bad rn, should handle if the atlas isn't big enough
     fn prepare_atlas(&mut self, lines: &[&DisplayCellLine]) {        
         for line in lines {
             for cell in &line.cells {
-                self.atlas.insert(cell.ftg.size(), cell.ftg.id(), cell.ftg.data()).unwrap();
+                for ftg in &cell.glyphs {
+                    self.atlas.insert(ftg.size(), ftg.id(), ftg.data()).unwrap();
+                }
             }
         }
     }
     
-    fn get_vertices_for_cell(&self, cell: &DisplayCell, display_cursor: bool, cell_size: RectSize, delta_height: u32, x: u32, y: u32) -> (Option<[BgVertex; 6]>, [CharVertex; 6]) {
-        let actual_x = x as i32;
-        let actual_y = y as i32;
+    // Colours of the glyph and of the background of a cell, once its attributes are applied.
+    fn get_colours_for_cell(&self, cell: &DisplayCell, cursor: Option<CursorDrawing>) -> ([f32; 4], Option<[f32; 4]>) {
+        // Only the block cursor covers the cell, the other ones are drawn over it.
+        if cell.is_cursor && cursor == Some(CursorDrawing::Shape(CursorShape::Block)) {
+            return (
+                SimpleColor::Black.to_color().to_opengl_color(),
+                Some(DEFAULT_FG.to_opengl_color())
+            );
+        }
         
-        let background_x = actual_x;
-        let background_y = actual_y;
+        let attributes = cell.attributes;
+        let default_bg = [DEFAULT_BG.0, DEFAULT_BG.1, DEFAULT_BG.2, 1.0];
         
-        let tex_rect = self.atlas.get(cell.ftg.id()).unwrap();
-        let cell_width = cell_size.width;
-        let cell_height = cell_size.height;
+        let mut fg_colour = cell.fg_color.to_opengl_color();
+        let mut bg_colour = cell.bg_color.map(Color::to_opengl_color);
+        
+        // Selected cells are shown reversed, or back to normal if they already are.
+        if attributes.contains(Attributes::REVERSE) != cell.selected {
+            bg_colour = Some(fg_colour);
+            fg_colour = cell.bg_color.map_or(default_bg, Color::to_opengl_color);
+        }
+        
+        // Dim text is halfway between its colour and the colour behind it.
+        if attributes.contains(Attributes::DIM) {
+            let behind = bg_colour.unwrap_or(default_bg);
+            
+            for (channel, behind) in fg_colour.iter_mut().zip(&behind).take(3) {
+                *channel = (*channel + behind) / 2.0;
+            }
+        }
+        
+        if attributes.contains(Attributes::HIDDEN) {
+            fg_colour[3] = 0.0;
+        }
+        
+        (fg_colour, bg_colour)
+    }
+    
+    fn get_vertices_for_rect(&self, x: i32, y: i32, width: u32, height: u32, colour: [f32; 4]) -> [BgVertex; 6] {
+        let RectSize {
+            height: screen_height,
+            width: screen_width
+        } = self.dimensions;
+        
+        let top_left_x = ((x as f32 / screen_width as f32) - 0.5 ) * 2.0;
+        let top_left_y = ((y as f32 / screen_height as f32) - 0.5 ) * -2.0;
+        
+        let bottom_right_x = (((x + width as i32) as f32 / screen_width as f32) - 0.5 ) * 2.0;
+        let bottom_right_y = (((y + height as i32) as f32 / screen_height as f32) - 0.5 ) * -2.0;
         
-        let delta_cell_y = cell_height as i32 - tex_rect.size.height as i32;
+        [
+            BgVertex {
+                position: [top_left_x, top_left_y],
+                colour
+            },
+            BgVertex {
+                position: [top_left_x, bottom_right_y],
+                colour
+            },
+            BgVertex {
+                position: [bottom_right_x, top_left_y],
+                colour
+            },
+            BgVertex {
+                position: [top_left_x, bottom_right_y],
+                colour
+            },
+            BgVertex {
+                position: [bottom_right_x, top_left_y],
+                colour
+            },
+            BgVertex {
+                position: [bottom_right_x, bottom_right_y],
+                colour
+            }
+        ]
+    }
+    
+    // Underline and strikethrough lines of a cell, drawn over the glyph.
+    fn get_decoration_vertices_for_cell(&self, cell: &DisplayCell, fg_colour: [f32; 4], cell_size: RectSize, delta_height: u32, x: u32, y: u32) -> Vec<BgVertex> {
+        let mut vertices = vec![];
+        
+        if cell.attributes.contains(Attributes::HIDDEN) {
+            return vertices;
+        }
+        
+        let baseline = (y + cell_size.height).saturating_sub(delta_height);
+        let thickness = (cell_size.height / 14).max(1);
+        
+        let hovered = cell.hyperlink.is_some() && cell.hyperlink == self.hovered_hyperlink;
+        
+        if cell.attributes.contains(Attributes::UNDERLINE) || hovered {
+            let underline_y = (baseline + 1).min(y + cell_size.height - thickness);
+            
+            vertices.extend(&self.get_vertices_for_rect(x as i32, underline_y as i32, cell_size.width, thickness, fg_colour));
+        }
+        
+        if cell.attributes.contains(Attributes::STRIKETHROUGH) {
+            let strikethrough_y = baseline.saturating_sub((cell_size.height - delta_height) / 3);
+            
+            vertices.extend(&self.get_vertices_for_rect(x as i32, strikethrough_y as i32, cell_size.width, thickness, fg_colour));
+        }
+        
+        vertices
+    }
+    
+    // Cursors which don't cover their cell: underline, bar, or the outline of a block.
+    fn get_cursor_vertices_for_cell(&self, cursor: CursorDrawing, cell_size: RectSize, x: u32, y: u32) -> Vec<BgVertex> {
+        let mut vertices = vec![];
+        let colour = DEFAULT_FG.to_opengl_color();
+        let thickness = (cell_size.height / 14).max(1);
+        
+        let (x, y) = (x as i32, y as i32);
+        let RectSize { width, height } = cell_size;
+        
+        match cursor {
+            CursorDrawing::Shape(CursorShape::Block) => {}
+            CursorDrawing::Shape(CursorShape::Underline) => {
+                let underline_y = y + (height - thickness) as i32;
+                
+                vertices.extend(&self.get_vertices_for_rect(x, underline_y, width, thickness, colour));
+            }
+            CursorDrawing::Shape(CursorShape::Bar) => {
+                let bar_width = (width / 8).max(1);
+                
+                vertices.extend(&self.get_vertices_for_rect(x, y, bar_width, height, colour));
+            }
+            CursorDrawing::Hollow => {
+                let right_x = x + (width - thickness) as i32;
+                let bottom_y = y + (height - thickness) as i32;
+                
+                vertices.extend(&self.get_vertices_for_rect(x, y, width, thickness, colour));
+                vertices.extend(&self.get_vertices_for_rect(x, bottom_y, width, thickness, colour));
+                vertices.extend(&self.get_vertices_for_rect(x, y, thickness, height, colour));
+                vertices.extend(&self.get_vertices_for_rect(right_x, y, thickness, height, colour));
+            }
+        }
+        
+        vertices
+    }
+    
+    fn get_vertices_for_glyph(&self, ftg: &FreeTypeGlyph, fg_colour: [f32; 4], cell_size: RectSize, delta_height: u32, x: i32, y: i32) -> [CharVertex; 6] {
+        let actual_x = x;
+        let actual_y = y;
+        
+        let tex_rect = self.atlas.get(ftg.id()).unwrap();
+        
+        let delta_cell_y = cell_size.height as i32 - tex_rect.size.height as i32;
         let actual_y = actual_y + delta_cell_y;
         
-        let delta_glyph_y = (cell.ftg.height - cell.ftg.bearing_y) / 64;
+        let delta_glyph_y = (ftg.height - ftg.bearing_y) / 64;
         let actual_y = actual_y + delta_glyph_y as i32;
         
         let actual_y = actual_y - delta_height as i32;
         
-        let delta_glyph_x = cell.ftg.bearing_x / 64;
+        let delta_glyph_x = ftg.bearing_x / 64;
         let actual_x = (actual_x as i64 + delta_glyph_x) as i32;
         
         let RectSize {
@@ -217,28 +375,16 @@ impl <'a> Drawer<'a> {
         let pos_top_left_x = ((actual_x as f32 / screen_width as f32) - 0.5 ) * 2.0;
         let pos_top_left_y = ((actual_y as f32 / screen_height as f32) - 0.5 ) * -2.0;
         
-        let background_top_left_x = ((background_x as f32 / screen_width as f32) - 0.5 ) * 2.0;
-        let background_top_left_y = ((background_y as f32 / screen_height as f32) - 0.5 ) * -2.0;
-        
         let pos_bottom_right_x = (((actual_x + tex_rect.size.width as i32) as f32 / screen_width as f32) - 0.5 ) * 2.0;
         let pos_bottom_right_y = (((actual_y + tex_rect.size.height as i32) as f32 / screen_height as f32) - 0.5 ) * -2.0;
         
-        let background_bottom_right_x = (((background_x + cell_width as i32) as f32 / screen_width as f32) - 0.5 ) * 2.0;
-        let background_bottom_right_y = (((background_y + cell_height as i32) as f32 / screen_height as f32) - 0.5 ) * -2.0;
-        
         let tex_top_left_x = tex_rect.top_left().x as f32 / atlas_width as f32;
         let tex_top_left_y = tex_rect.top_left().y as f32 / atlas_height as f32 * -1.0;
         
         let tex_bottom_right_x = tex_rect.bottom_right().x as f32 / atlas_width as f32;
         let tex_bottom_right_y = tex_rect.bottom_right().y as f32 / atlas_height as f32 * -1.0;
         
-        let fg_colour = if cell.is_cursor && display_cursor {
-            SimpleColor::Black.to_color().to_opengl_color()
-        } else {
-            cell.fg_color.to_opengl_color()
-        };
-        
-        let char_vertices = [
+        [
             CharVertex {
                 position: [pos_top_left_x, pos_top_left_y],
                 tex_coords: [tex_top_left_x, tex_top_left_y],
@@ -269,70 +415,64 @@ impl <'a> Drawer<'a> {
                 tex_coords: [tex_bottom_right_x, tex_bottom_right_y],
                 colour: fg_colour,
             }
-        ];
-        
-        let bg_colour = if cell.is_cursor && display_cursor {
-            Some(DEFAULT_FG.to_opengl_color())
-        } else {
-            match cell.bg_color {
-                None => None,
-                Some(colour) => Some(colour.to_opengl_color())
-            }
-        };
+        ]
+    }
+    
+    fn get_vertices_for_cell(&self, cell: &DisplayCell, cursor: Option<CursorDrawing>, cell_size: RectSize, delta_height: u32, x: u32, y: u32) -> (Option<[BgVertex; 6]>, Vec<CharVertex>, Vec<BgVertex>) {
+        let (fg_colour, bg_colour) = self.get_colours_for_cell(cell, cursor);
         
-        let background_vertices = match bg_colour {
-            None => None,
-            Some(bg_colour) => Some(
-                    [
-                       BgVertex {
-                           position: [background_top_left_x, background_top_left_y],
-                           colour: bg_colour
-                       },
-                       BgVertex {
-                           position: [background_top_left_x, background_bottom_right_y],
-                           colour: bg_colour
-                       },
-                       BgVertex {
-                           position: [background_bottom_right_x, background_top_left_y],
-                           colour: bg_colour
-                       },
-                       BgVertex {
-                           position: [background_top_left_x, background_bottom_right_y],
-                           colour: bg_colour
-                       },
-                       BgVertex {
-                           position: [background_bottom_right_x, background_top_left_y],
-                           colour: bg_colour
-                       },
-                       BgVertex {
-                           position: [background_bottom_right_x, background_bottom_right_y],
-                           colour: bg_colour
-                       }
-                   ]
-                )
-        };
+        // Glyphs of a grapheme follow each other, the first one starting at the cell.
+        let mut pen_x = x as i32;
+        let mut char_vertices: Vec<CharVertex> = Vec::with_capacity(cell.glyphs.len() * 6);
         
-        (background_vertices, char_vertices)
+        for ftg in cell.glyphs.iter() {
+            char_vertices.extend(&self.get_vertices_for_glyph(ftg, fg_colour, cell_size, delta_height, pen_x, y as i32));
+            pen_x += (ftg.advance_x() / 64) as i32;
+        }
+        
+        let background_vertices = bg_colour.map(|bg_colour| {
+            self.get_vertices_for_rect(x as i32, y as i32, cell_size.width, cell_size.height, bg_colour)
+        });
+        
+        let mut decoration_vertices = self.get_decoration_vertices_for_cell(cell, fg_colour, cell_size, delta_height, x, y);
+        
+        if let Some(cursor) = cursor.filter(|_| cell.is_cursor) {
+            decoration_vertices.append(&mut self.get_cursor_vertices_for_cell(cursor, cell_size, x, y));
+        }
+        
+        (background_vertices, char_vertices, decoration_vertices)
     }
     
-    fn get_vertices_for_line(&self, line: &DisplayCellLine, display_cursor: bool, cell_size: RectSize, delta_height: u32, y: u32) -> (Vec<BgVertex>, Vec<CharVertex>) {
+    fn get_vertices_for_line(&self, line: &DisplayCellLine, cursor: Option<CursorDrawing>, cell_size: RectSize, delta_height: u32, y: u32) -> (Vec<BgVertex>, Vec<CharVertex>, Vec<BgVertex>) {
         let mut x = 0;
         
         let mut bg_vertices: Vec<BgVertex> = Vec::with_capacity(line.cells.len()); 
         let mut char_vertices: Vec<CharVertex> = Vec::with_capacity(line.cells.len());  
+        let mut decoration_vertices: Vec<BgVertex> = vec![];
         
         for cell in line.cells.iter() {
-            let vertices = self.get_vertices_for_cell(cell, display_cursor, cell_size, delta_height, x, y);
+            // Wide characters take the space of two cells.
+            let cell_size = if cell.wide {
+                RectSize {
+                    width: cell_size.width * 2,
+                    ..cell_size
+                }
+            } else {
+                cell_size
+            };
+            
+            let vertices = self.get_vertices_for_cell(cell, cursor, cell_size, delta_height, x, y);
             
             if let Some(bg) = &vertices.0 {
                 bg_vertices.extend(bg);
             }
             
             char_vertices.extend(&vertices.1);
+            decoration_vertices.extend(&vertices.2);
             x += cell_size.width;
         }
         
-        (bg_vertices, char_vertices)
+        (bg_vertices, char_vertices, decoration_vertices)
     }
     
     fn draw_char_vertex(&self, vertex_buffer: &VertexBuffer<CharVertex>, frame: &mut Frame, uniforms: impl Uniforms) {
@@ -365,7 +505,7 @@ impl <'a> Drawer<'a> {
              .unwrap();
      }
     
-    pub fn render_lines(&mut self, lines: &[DisplayCellLine], display_cursor: bool,
+    pub fn render_lines(&mut self, lines: &[DisplayCellLine], cursor: Option<CursorDrawing>,
         cell_size: RectSize, delta_height: u32, display: &Display, frame: &mut Frame) {
         
         let cell_height = cell_size.height;
@@ -380,17 +520,20 @@ impl <'a> Drawer<'a> {
         let mut current_height = delta_height;
         let mut bg_vertices: Vec<BgVertex> = vec![];
         let mut char_vertices: Vec<CharVertex> = vec![];
+        let mut decoration_vertices: Vec<BgVertex> = vec![];
 
         for line in lines_to_render {
-            let mut vertices = self.get_vertices_for_line(line, display_cursor, cell_size, delta_height, current_height);
+            let mut vertices = self.get_vertices_for_line(line, cursor, cell_size, delta_height, current_height);
             
             bg_vertices.append(&mut vertices.0);
             char_vertices.append(&mut vertices.1);
+            decoration_vertices.append(&mut vertices.2);
             current_height += cell_height;
         }
         
         let bg_vertex_buffer = VertexBuffer::new(display, &bg_vertices).unwrap();
         let char_vertex_buffer = VertexBuffer::new(display, &char_vertices).unwrap();
+        let decoration_vertex_buffer = VertexBuffer::new(display, &decoration_vertices).unwrap();
             
         let sampler = self.atlas.atlas
             .sampled()
@@ -402,5 +545,6 @@ impl <'a> Drawer<'a> {
         
         self.draw_bg_vertex(&bg_vertex_buffer, frame);
         self.draw_char_vertex(&char_vertex_buffer, frame, char_uniforms);
+        self.draw_bg_vertex(&decoration_vertex_buffer, frame);
     }
 }
@@ -18,12 +18,22 @@ extern {
         destination: *mut FT_Bitmap,
         alignment: FT_Int,
     ) -> FT_Error;
+    fn FT_GlyphSlot_Embolden(slot: FT_GlyphSlot);
+    fn FT_GlyphSlot_Oblique(slot: FT_GlyphSlot);
 }
 
 
 
 type FTResult<T> = Result<T, FT_Error>;
 
+// Style applied by FreeType to a glyph before rendering it, used when a font file for this style
+// is missing.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SyntheticStyle {
+    pub bold: bool,
+    pub italic: bool
+}
+
 pub fn init_freetype() -> FTResult<FT_Library> {
     let mut freetype_lib: FT_Library = std::ptr::null_mut();
     let error = unsafe {
@@ -78,7 +88,14 @@ pub fn set_char_size(face: FT_Face, size: i64) -> FTResult<()> {
     }
 }
 
-pub fn render_glyph(_lib: FT_Library, face: FT_Face, glyph_index: u32) -> FTResult<FreeTypeGlyph> {
+pub fn has_glyph(face: FT_Face, character: char) -> bool {
+    unsafe {
+        FT_Get_Char_Index(face, character as FT_ULong) != 0
+    }
+}
+
+// `face_id` identifies the face in the atlas, since glyph indices are only unique inside a face.
+pub fn render_glyph(_lib: FT_Library, face: FT_Face, face_id: u32, glyph_index: u32, synthetic: SyntheticStyle) -> FTResult<FreeTypeGlyph> {
     let error = unsafe {
         FT_Load_Glyph(
             face,
@@ -92,6 +109,16 @@ pub fn render_glyph(_lib: FT_Library, face: FT_Face, glyph_index: u32) -> FTResu
         return Err(error);
     }
     
+    unsafe {
+        if synthetic.bold {
+            FT_GlyphSlot_Embolden((*face).glyph);
+        }
+        
+        if synthetic.italic {
+            FT_GlyphSlot_Oblique((*face).glyph);
+        }
+    }
+    
     let error = unsafe {
         FT_Render_Glyph(
             (*face).glyph,
@@ -127,6 +154,7 @@ pub fn render_glyph(_lib: FT_Library, face: FT_Face, glyph_index: u32) -> FTResu
             
         FreeTypeGlyph {
             id: glyph_index,
+            face_id,
             buffer,
             rows: bitmap.rows,
             pitch: bitmap.pitch.abs() as u32,
@@ -142,11 +170,11 @@ pub fn render_glyph(_lib: FT_Library, face: FT_Face, glyph_index: u32) -> FTResu
     Ok(glyph)
 }
 
-pub fn render_glyphs(lib: FT_Library, face: FT_Face, glyphs: &[u32]) -> FTResult<Vec<FreeTypeGlyph>> {
+pub fn render_glyphs(lib: FT_Library, face: FT_Face, face_id: u32, glyphs: &[u32], synthetic: SyntheticStyle) -> FTResult<Vec<FreeTypeGlyph>> {
     let mut results: Vec<FreeTypeGlyph> = vec![];
     
     for &glyph in glyphs.iter() {
-        let result = render_glyph(lib, face, glyph)?;
+        let result = render_glyph(lib, face, face_id, glyph, synthetic)?;
         results.push(result);
     }
     
@@ -156,6 +184,7 @@ pub fn render_glyphs(lib: FT_Library, face: FT_Face, glyphs: &[u32]) -> FTResult
 #[derive(Debug, Clone)]
 pub struct FreeTypeGlyph {
     id: u32,
+    face_id: u32,
     buffer: Vec<u8>,
     rows: u32,
     pitch: u32,
@@ -189,11 +218,17 @@ impl FreeTypeGlyph {
         }
     }
     
+    // Glyph indices fit in 16 bits, so the face id goes in the upper ones.
     pub fn id(&self) -> u32 {
-        self.id
+        self.face_id << 16 | self.id
     }
     
     pub fn data(&self) -> &[u8] {
         &self.buffer
     }
+    
+    // In 26.6 format, like the other metrics.
+    pub fn advance_x(&self) -> i64 {
+        self.advance_x
+    }
 }
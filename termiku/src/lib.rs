@@ -7,6 +7,7 @@
 extern crate glium;
 
 pub mod atlas;
+pub mod clipboard;
 pub mod config;
 pub mod control;
 // Allowed because of the implement_vertex! macro which will trigger this clippy lint, outside
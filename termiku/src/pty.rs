@@ -119,6 +119,24 @@ impl Pty {
         mem::forget(self);
         fds
     }
+
+    /// Sets the size (cells and pixels) of this pseudoterminal.
+    /// The kernel then sends a `SIGWINCH` to the foreground process group of the pts device.
+    ///
+    /// See also [tty_ioctl(4)].
+    ///
+    /// [tty_ioctl(4)]: http://man7.org/linux/man-pages/man4/tty_ioctl.4.html
+    pub fn resize(&self, winsize: libc::winsize) -> io::Result<()> {
+        let res = unsafe {
+            libc::ioctl(self.fds.ptmx, libc::TIOCSWINSZ, &winsize)
+        };
+
+        if res == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl io::Read for Pty {
@@ -0,0 +1,95 @@
+use crate::control::control_type::CharacterSet;
+
+// Character sets designated as G0 to G3, as defined by ECMA-35.
+// G0 or G1 is invoked into GL by SI and SO, and is used for the printed characters, unless a
+// single shift invokes G2 or G3 for the next one.
+#[derive(Copy, Clone, Debug)]
+pub struct Charsets {
+    sets: [CharacterSet; 4],
+    gl: usize,
+    single_shift: Option<usize>
+}
+
+impl Default for Charsets {
+    fn default() -> Self {
+        Self {
+            sets: [CharacterSet::Ascii; 4],
+            gl: 0,
+            single_shift: None
+        }
+    }
+}
+
+impl Charsets {
+    pub fn designate(&mut self, index: usize, set: CharacterSet) {
+        if let Some(slot) = self.sets.get_mut(index) {
+            *slot = set;
+        }
+    }
+    
+    // SI and SO
+    pub fn invoke(&mut self, index: usize) {
+        self.gl = index;
+    }
+    
+    // SS2 and SS3
+    pub fn single_shift(&mut self, index: usize) {
+        self.single_shift = Some(index);
+    }
+    
+    // Translate a printed character, through the set invoked for it.
+    pub fn translate(&mut self, character: char) -> char {
+        let index = self.single_shift.take().unwrap_or(self.gl);
+        
+        match self.sets[index] {
+            CharacterSet::Ascii => character,
+            CharacterSet::DecSpecialGraphics => translate_dec_special_graphics(character),
+            CharacterSet::Uk => translate_uk(character)
+        }
+    }
+}
+
+fn translate_uk(character: char) -> char {
+    match character {
+        '#' => '£',
+        _ => character
+    }
+}
+
+fn translate_dec_special_graphics(character: char) -> char {
+    match character {
+        '_' => ' ',
+        '`' => '◆',
+        'a' => '▒',
+        'b' => '␉',
+        'c' => '␌',
+        'd' => '␍',
+        'e' => '␊',
+        'f' => '°',
+        'g' => '±',
+        'h' => '␤',
+        'i' => '␋',
+        'j' => '┘',
+        'k' => '┐',
+        'l' => '┌',
+        'm' => '└',
+        'n' => '┼',
+        'o' => '⎺',
+        'p' => '⎻',
+        'q' => '─',
+        'r' => '⎼',
+        's' => '⎽',
+        't' => '├',
+        'u' => '┤',
+        'v' => '┴',
+        'w' => '┬',
+        'x' => '│',
+        'y' => '≤',
+        'z' => '≥',
+        '{' => 'π',
+        '|' => '≠',
+        '}' => '£',
+        '~' => '·',
+        _ => character
+    }
+}
@@ -1,3 +1,5 @@
+use crate::control::control_type::ClipboardTarget;
+
 #[derive(Clone, Debug)]
 pub struct ScreenEvent {
     pub terminal_id: usize,
@@ -6,6 +8,12 @@ pub struct ScreenEvent {
 
 #[derive(Clone, Debug)]
 pub enum ScreenEventType {
-    PlayYoutubeVideo(String)
+    PlayYoutubeVideo(String),
+    SetTitle(String),
+    // Clipboard accesses asked by the program, left to the window to carry out if allowed
+    SetClipboard(ClipboardTarget, String),
+    QueryClipboard(ClipboardTarget),
+    // Bytes to write to the pty of the terminal, in reply to a control sequence
+    Reply(Vec<u8>)
 }
 
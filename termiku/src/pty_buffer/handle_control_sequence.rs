@@ -5,10 +5,30 @@ use super::*;
 
 use ControlType::*;
 
+// Reply to DA: a VT220, with ANSI colours.
+const DEVICE_ATTRIBUTES: &str = "\x1b[?62;22c";
+
 impl Screen {
     #[allow(clippy::cognitive_complexity)] // I won't comment on this.
     pub fn handle_control_sequence(&mut self, control: ControlType, rasterizer: &mut Rasterizer) {
-        println!("control sequence received! {:?}", control);
+        // The contents of the clipboard stay out of the log.
+        match &control {
+            SetClipboard(target, contents) => {
+                println!("control sequence received! SetClipboard({:?}, {} bytes)", target, contents.len());
+            },
+            _ => println!("control sequence received! {:?}", control)
+        }
+        
+        // Moving the cursor or changing the cells of its line cancels a pending wrap.
+        match &control {
+            CursorUp(_) | CursorDown(_) | CursorRight(_) | CursorLeft(_) |
+            CursorNextLine(_) | CursorPrecedingLine(_) | CursorCharacterAbsolute(_) |
+            CursorPosition(_, _) | EraseInPage(_) | EraseInLine(_) | InsertLine(_) |
+            DeleteLine(_) | InsertCharacter(_) | DeleteCharacter(_) | EraseCharacter(_) => {
+                self.cursor.wrap_pending = false;
+            },
+            _ => {}
+        }
         
         match control {
         
@@ -141,10 +161,16 @@ impl Screen {
                     } else {
                         row
                     }
-                };                
+                };
+                
+                // With DECOM, rows are counted from the top of the scroll region, and the cursor
+                // can't leave it.
+                if self.state.origin_mode {
+                    *cursor_y = (*cursor_y + self.scroll_region.top - 1).min(self.scroll_region.bottom);
+                }
             },
             
-            // Erase cells of the current page.
+            // Erase cells of the current page, with the current background color.
             // If parameter = 0, erase everything after and including the cursor.
             // If parameter = 1, erase everything before and including the cursor.
             // If parameter = 2, erase everything.
@@ -153,7 +179,7 @@ impl Screen {
                     0 => {
                         let mut new_line = CellLine::new(
                             self.line_cell_width, 
-                            CellProperties::new()
+                            self.erase_properties()
                         );
                         
                         new_line.rasterize(rasterizer);
@@ -162,16 +188,15 @@ impl Screen {
                             self.screen_lines[index] = new_line.clone();
                         }
                         
-                        for index in self.cursor.position.x .. self.line_cell_width {
-                            self.screen_lines[self.cursor.position.y - 1].cells[index] = Cell::empty(CellProperties::new())
-                        }
+                        self.erase_cells(self.cursor.position.x - 1, self.line_cell_width, rasterizer);
                         
-                        self.screen_lines[self.cursor.position.y - 1].rasterize(rasterizer);
+                        // The end of the line is gone, so it can't continue on the next one.
+                        self.screen_lines[self.cursor.position.y - 1].wrapped = false;
                     },
                     1 => {
                         let mut new_line = CellLine::new(
                             self.line_cell_width, 
-                            CellProperties::new()
+                            self.erase_properties()
                         );
                         
                         new_line.rasterize(rasterizer);
@@ -180,16 +205,12 @@ impl Screen {
                             self.screen_lines[index] = new_line.clone();
                         }
                         
-                        for index in 0 .. self.cursor.position.x - 1 {
-                            self.screen_lines[self.cursor.position.y - 1].cells[index] = Cell::empty(CellProperties::new())
-                        }
-                        
-                        self.screen_lines[self.cursor.position.y - 1].rasterize(rasterizer);
+                        self.erase_cells(0, self.cursor.position.x, rasterizer);
                     },
                     2 => {
                         let mut new_line = CellLine::new(
                             self.line_cell_width, 
-                            CellProperties::new()
+                            self.erase_properties()
                         );
                         
                         new_line.rasterize(rasterizer);
@@ -202,68 +223,127 @@ impl Screen {
                 }
             }
             
-            // Delete the current and the n-1 following lines, then make the the cursor go to
-            // column = 1.
+            // Erase cells of the current line, with the current background color.
+            // If parameter = 0, erase everything after and including the cursor.
+            // If parameter = 1, erase everything before and including the cursor.
+            // If parameter = 2, erase the whole line.
+            EraseInLine(parameter) => {
+                let cursor_x = self.cursor.position.x;
+                
+                match parameter {
+                    0 => {
+                        self.erase_cells(cursor_x - 1, self.line_cell_width, rasterizer);
+                        
+                        // The end of the line is gone, so it can't continue on the next one.
+                        self.screen_lines[self.cursor.position.y - 1].wrapped = false;
+                    },
+                    1 => self.erase_cells(0, cursor_x, rasterizer),
+                    2 => {
+                        self.erase_cells(0, self.line_cell_width, rasterizer);
+                        self.screen_lines[self.cursor.position.y - 1].wrapped = false;
+                    },
+                    _ => {}
+                }
+            },
+            
+            // Insert n blank lines at the current line, pushing it and the following lines down
+            // inside the scroll region, then make the cursor go to column = 1.
             // If = 0, treats it as n = 1.
-            // If the number of line to delete would be too high and go past the number of lines,
-            // delete until the last line.
+            InsertLine(parameter) => {
+                self.insert_lines(parameter.max(1) as usize, rasterizer);
+                
+                self.cursor.position.x = 1;
+            },
+            
+            // Delete the current and the n-1 following lines, pulling the lines below up inside
+            // the scroll region, then make the the cursor go to column = 1.
+            // If = 0, treats it as n = 1.
+            // If the number of line to delete would be too high and go past the bottom of the
+            // scroll region, delete until the bottom of the scroll region.
             DeleteLine(parameter) => {
-                let number_to_delete = if parameter == 0 {
-                    1
-                } else {
-                    parameter
-                };
+                self.delete_lines(parameter.max(1) as usize, rasterizer);
                 
-                let cursor_y = self.cursor.position.y;
+                self.cursor.position.x = 1;
+            },
+            
+            // Insert n blank cells at the cursor, pushing the following cells right.
+            // If = 0, treats it as n = 1.
+            InsertCharacter(parameter) => {
+                self.insert_cells(parameter.max(1) as usize, rasterizer);
+            },
+            
+            // Delete the n cells starting at the cursor, pulling the following cells left.
+            // If = 0, treats it as n = 1.
+            DeleteCharacter(parameter) => {
+                self.delete_cells(parameter.max(1) as usize, rasterizer);
+            },
+            
+            // Erase the n cells starting at the cursor, without moving the other cells.
+            // If = 0, treats it as n = 1.
+            EraseCharacter(parameter) => {
+                let start = self.cursor.position.x - 1;
                 
-                let number_to_delete = if (number_to_delete - 1) as usize + cursor_y > self.line_cell_height {
-                    (self.line_cell_height - cursor_y + 1) as u16
-                } else {
-                    number_to_delete
+                self.erase_cells(start, start + parameter.max(1) as usize, rasterizer);
+            },
+            
+            // Scroll the lines of the scroll region n lines up. The cursor doesn't move.
+            // If = 0, treats it as n = 1.
+            ScrollUp(parameter) => {
+                self.scroll_up(parameter.max(1) as usize, rasterizer);
+            },
+            
+            // Scroll the lines of the scroll region n lines down. The cursor doesn't move.
+            // If = 0, treats it as n = 1.
+            ScrollDown(parameter) => {
+                self.scroll_down(parameter.max(1) as usize, rasterizer);
+            },
+            
+            // Set the lines between which the screen scrolls, then make the cursor go to the
+            // first line and first column.
+            // If the bottom is 0 or past the last line, use the last line.
+            // Ignored if the region would be less than 2 lines.
+            SetTopAndBottomMargins(top, bottom) => {
+                let top = (top as usize).max(1);
+                let bottom = match bottom as usize {
+                    0 => self.line_cell_height,
+                    bottom => bottom.min(self.line_cell_height)
                 };
                 
-                for _ in 0..number_to_delete {
-                    self.screen_lines.remove(cursor_y - 1);
-                }
-                
-                while self.screen_lines.len() < self.line_cell_height {
-                    let mut new_line = CellLine::new(self.line_cell_width, CellProperties::new());
-                    new_line.rasterize(rasterizer);
-                    self.screen_lines.push(new_line);
+                if top < bottom {
+                    self.scroll_region = ScrollRegion { top, bottom };
+                    self.home_cursor();
                 }
-                
-                assert!(self.screen_lines.len() == self.line_cell_height);
-                
+            },
+            
+            // Make the cursor go one line down, scrolling if at the bottom of the scroll region.
+            Index => {
+                self.next_line(rasterizer);
+            },
+            
+            // Same as Index, but also make the cursor go to the first column.
+            NextLine => {
+                self.next_line(rasterizer);
                 self.cursor.position.x = 1;
-            }
+            },
             
-            // Set the mode in which the terminal will operate in. Currently only implements
-            // an alternative buffer (smcup).
-            SetMode(parameters) => {
-                let mut index = 0usize;
-                
-                loop {
-                    if index >= parameters.len() {
-                        break
-                    }
-                    
-                    index = self.exec_sm_property(&parameters, index);
+            // Make the cursor go one line up, scrolling if at the top of the scroll region.
+            ReverseLineFeed => {
+                self.previous_line(rasterizer);
+            },
+            
+            // Set the modes in which the terminal will operate in.
+            SetMode(mode_type, parameters) => {
+                for &mode in parameters.iter() {
+                    self.set_mode(mode_type, mode, true, rasterizer);
                 }
             },
             
-            // Reset the mode in which the terminal will operate in. Currently only implements
-            // an alternative buffer (rmcup).
-            ResetMode(parameters) => {
-                let mut index = 0usize;
-                
-                loop {
-                    if index >= parameters.len() {
-                        break
-                    }
-                    
-                    index = self.exec_rm_property(&parameters, index);
+            // Reset the modes in which the terminal will operate in.
+            ResetMode(mode_type, parameters) => {
+                for &mode in parameters.iter() {
+                    self.set_mode(mode_type, mode, false, rasterizer);
                 }
-            }
+            },
             
             // One of the heaviest control sequence, which changes the way characters are now
             // printed on screen.
@@ -298,6 +378,103 @@ impl Screen {
                 self.cursor.restore();
             },
             
+            SaveCursorAndAttributes => {
+                self.save_cursor();
+            },
+            
+            RestoreCursorAndAttributes => {
+                self.restore_cursor();
+            },
+            
+            KeypadApplicationMode => {
+                self.state.application_keypad = true;
+            },
+            
+            KeypadNumericMode => {
+                self.state.application_keypad = false;
+            },
+            
+            ResetToInitialState => {
+                self.reset(rasterizer);
+            },
+            
+            DesignateCharacterSet(index, set) => {
+                self.state.charsets.designate(index, set);
+            },
+            
+            SingleShiftTwo => {
+                self.state.charsets.single_shift(2);
+            },
+            
+            SingleShiftThree => {
+                self.state.charsets.single_shift(3);
+            },
+            
+            DeviceAttributes(0) => {
+                self.reply(DEVICE_ATTRIBUTES);
+            },
+            
+            // Identify as a VT220, with the version of Termiku as the firmware version.
+            SecondaryDeviceAttributes(0) => {
+                self.reply(&format!("\x1b[>1;{};0c", get_version_number()));
+            },
+            
+            // Termiku is always fine.
+            DeviceStatusReport(5) => {
+                self.reply("\x1b[0n");
+            },
+            
+            // CPR, reporting the cursor position
+            DeviceStatusReport(6) => {
+                let (row_number, column_number) = self.get_position_pointed_by_cursor();
+                
+                // With DECOM, rows are counted from the top of the scroll region.
+                let row_number = if self.state.origin_mode {
+                    row_number.saturating_sub(self.scroll_region.top - 1)
+                } else {
+                    row_number
+                };
+                
+                self.reply(&format!("\x1b[{};{}R", row_number + 1, column_number + 1));
+            },
+            
+            RequestMode(mode_type, mode) => {
+                self.report_mode(mode_type, mode);
+            },
+            
+            SetCursorStyle(parameter) => {
+                if let Some(style) = CursorStyle::from_parameter(parameter) {
+                    self.state.cursor_style = style;
+                }
+            },
+            
+            ReportVersion => {
+                self.reply(&format!("\x1bP>|termiku({})\x1b\\", env!("CARGO_PKG_VERSION")));
+            },
+            
+            WindowManipulation(parameters) => {
+                self.exec_window_manipulation(&parameters);
+            },
+            
+            SetIconNameAndWindowTitle(title) | SetWindowTitle(title) => {
+                self.set_title(title);
+            },
+            
+            // There's no icon to give a name to.
+            SetIconName(_) => {},
+            
+            SetHyperlink(id, uri) => {
+                self.set_hyperlink(id, uri);
+            },
+            
+            SetClipboard(target, contents) => {
+                self.send_event(ScreenEventType::SetClipboard(target, contents));
+            },
+            
+            QueryClipboard(target) => {
+                self.send_event(ScreenEventType::QueryClipboard(target));
+            },
+            
             TermikuYoutubePlayback(data) => {
                 if data.iter().all(|x| URL_PADDINGLESS_BASE64_RANGE.contains(x)) {
                     self.sender.lock().unwrap().send(
@@ -313,44 +490,173 @@ impl Screen {
         }
     }
     
-    #[allow(clippy::single_match)]
-    fn exec_sm_property(&mut self, parameters: &[u16], index: usize) -> usize {
-        let property = parameters[index];
+    // Only the title stack operations are supported, and Termiku has no icon name to save, so
+    // only the window title is pushed and popped.
+    fn exec_window_manipulation(&mut self, parameters: &[u16]) {
+        let operation = parameters.get(0).copied().unwrap_or(0);
+        let target = parameters.get(1).copied().unwrap_or(0);
         
-        match property {
-            1049 => {
-                if !self.state.is_alternative {
-                    self.state.is_alternative = true;
-                    
-                    std::mem::swap(&mut self.cursor, &mut self.alternative_cursor);
-                    std::mem::swap(&mut self.screen_lines, &mut self.alternative_screen_lines);
+        // 0 is both the icon name and the window title, 1 the icon name and 2 the window title.
+        if target == 1 {
+            return;
+        }
+        
+        match operation {
+            22 => {
+                if self.title_stack.len() == TITLE_STACK_SIZE {
+                    self.title_stack.remove(0);
+                }
+                
+                self.title_stack.push(self.title.clone());
+            },
+            23 => {
+                if let Some(title) = self.title_stack.pop() {
+                    self.set_title(title);
                 }
             },
-            
             _ => {}
         }
-        
-        index + 1
     }
     
-    #[allow(clippy::single_match)]
-    fn exec_rm_property(&mut self, parameters: &[u16], index: usize) -> usize {
-        let property = parameters[index];
+    fn set_title(&mut self, title: String) {
+        self.title = title.clone();
+        self.send_event(ScreenEventType::SetTitle(title));
+    }
+    
+    // Write a reply to a control sequence to the pty.
+    fn reply(&self, reply: &str) {
+        self.send_event(ScreenEventType::Reply(reply.as_bytes().to_vec()));
+    }
+    
+    fn send_event(&self, event: ScreenEventType) {
+        self.sender.lock().unwrap().send(
+            ScreenEvent {
+                terminal_id: self.id,
+                event,
+            }
+        ).unwrap();
+    }
+    
+    // DECRPM, the reply to DECRQM: 0 if the mode isn't recognized, 1 if it's set and 2 if it's
+    // reset.
+    fn report_mode(&self, mode_type: ModeType, mode: u16) {
+        let value = match self.get_mode(mode_type, mode) {
+            Some(true) => 1,
+            Some(false) => 2,
+            None => 0
+        };
         
-        match property {
-            1049 => {
-                if self.state.is_alternative {
-                    self.state.is_alternative = false;
-                    
-                    std::mem::swap(&mut self.cursor, &mut self.alternative_cursor);
-                    std::mem::swap(&mut self.screen_lines, &mut self.alternative_screen_lines);
+        let prefix = match mode_type {
+            ModeType::Ansi => "",
+            ModeType::DecPrivate => "?"
+        };
+        
+        self.reply(&format!("\x1b[{}{};{}$y", prefix, mode, value));
+    }
+    
+    // Whether a mode is set, or None if it isn't supported.
+    // 1048 isn't a state, it's always reported as reset.
+    fn get_mode(&self, mode_type: ModeType, mode: u16) -> Option<bool> {
+        match (mode_type, mode) {
+            (ModeType::Ansi, 4) => Some(self.state.insert_mode),
+            (ModeType::Ansi, 20) => Some(self.state.new_line_mode),
+            (ModeType::DecPrivate, 1) => Some(self.state.application_cursor),
+            (ModeType::DecPrivate, 6) => Some(self.state.origin_mode),
+            (ModeType::DecPrivate, 7) => Some(self.state.auto_wrap),
+            (ModeType::DecPrivate, 25) => Some(self.state.cursor_visible),
+            (ModeType::DecPrivate, 47) | (ModeType::DecPrivate, 1047) | (ModeType::DecPrivate, 1049) => Some(self.state.is_alternative),
+            (ModeType::DecPrivate, 1000) => Some(self.state.mouse_tracking == MouseTracking::Normal),
+            (ModeType::DecPrivate, 1002) => Some(self.state.mouse_tracking == MouseTracking::ButtonEvent),
+            (ModeType::DecPrivate, 1003) => Some(self.state.mouse_tracking == MouseTracking::AnyEvent),
+            (ModeType::DecPrivate, 1005) => Some(self.state.mouse_encoding == MouseEncoding::Utf8),
+            (ModeType::DecPrivate, 1006) => Some(self.state.mouse_encoding == MouseEncoding::Sgr),
+            (ModeType::DecPrivate, 1015) => Some(self.state.mouse_encoding == MouseEncoding::Urxvt),
+            (ModeType::DecPrivate, 1048) => Some(false),
+            (ModeType::DecPrivate, 2004) => Some(self.state.bracketed_paste),
+            _ => None
+        }
+    }
+    
+    fn set_mode(&mut self, mode_type: ModeType, mode: u16, value: bool, rasterizer: &mut Rasterizer) {
+        match (mode_type, mode) {
+            // IRM
+            (ModeType::Ansi, 4) => self.state.insert_mode = value,
+            // LNM
+            (ModeType::Ansi, 20) => self.state.new_line_mode = value,
+            // DECCKM
+            (ModeType::DecPrivate, 1) => self.state.application_cursor = value,
+            // DECOM, which also moves the cursor to the new origin
+            (ModeType::DecPrivate, 6) => {
+                self.state.origin_mode = value;
+                self.home_cursor();
+            },
+            // DECAWM
+            (ModeType::DecPrivate, 7) => self.state.auto_wrap = value,
+            // DECTCEM
+            (ModeType::DecPrivate, 25) => self.state.cursor_visible = value,
+            // Mouse tracking, which is turned off by resetting any of its modes
+            (ModeType::DecPrivate, 1000) => self.set_mouse_tracking(MouseTracking::Normal, value),
+            (ModeType::DecPrivate, 1002) => self.set_mouse_tracking(MouseTracking::ButtonEvent, value),
+            (ModeType::DecPrivate, 1003) => self.set_mouse_tracking(MouseTracking::AnyEvent, value),
+            // Mouse encodings, going back to X10 when the one in use is reset
+            (ModeType::DecPrivate, 1005) => self.set_mouse_encoding(MouseEncoding::Utf8, value),
+            (ModeType::DecPrivate, 1006) => self.set_mouse_encoding(MouseEncoding::Sgr, value),
+            (ModeType::DecPrivate, 1015) => self.set_mouse_encoding(MouseEncoding::Urxvt, value),
+            // Alternative screen
+            (ModeType::DecPrivate, 47) => {
+                if value {
+                    self.enter_alternative_screen(false, rasterizer);
+                } else {
+                    self.leave_alternative_screen(false, rasterizer);
                 }
             },
-            
+            // Alternative screen, cleared when leaving it
+            (ModeType::DecPrivate, 1047) => {
+                if value {
+                    self.enter_alternative_screen(false, rasterizer);
+                } else {
+                    self.leave_alternative_screen(true, rasterizer);
+                }
+            },
+            // Save the cursor like DECSC, and restore it like DECRC
+            (ModeType::DecPrivate, 1048) => {
+                if value {
+                    self.save_cursor();
+                } else {
+                    self.restore_cursor();
+                }
+            },
+            // 1048 and 1047 combined, with the alternative screen cleared when entering it (smcup
+            // and rmcup)
+            (ModeType::DecPrivate, 1049) => {
+                if value {
+                    self.save_cursor();
+                    self.enter_alternative_screen(true, rasterizer);
+                } else {
+                    self.leave_alternative_screen(false, rasterizer);
+                    self.restore_cursor();
+                }
+            },
+            // Bracketed paste
+            (ModeType::DecPrivate, 2004) => self.state.bracketed_paste = value,
             _ => {}
         }
-        
-        index + 1
+    }
+    
+    fn set_mouse_tracking(&mut self, tracking: MouseTracking, value: bool) {
+        self.state.mouse_tracking = if value {
+            tracking
+        } else {
+            MouseTracking::None
+        };
+    }
+    
+    fn set_mouse_encoding(&mut self, encoding: MouseEncoding, value: bool) {
+        if value {
+            self.state.mouse_encoding = encoding;
+        } else if self.state.mouse_encoding == encoding {
+            self.state.mouse_encoding = MouseEncoding::X10;
+        }
     }
     
     fn exec_sgr_property(&mut self, parameters: &[u16], index: usize) -> usize {
@@ -361,6 +667,26 @@ impl Screen {
         match property {
             0 => self.reset_graphics(),
             
+            1 => self.set_attributes(Attributes::BOLD),
+            2 => self.set_attributes(Attributes::DIM),
+            3 => self.set_attributes(Attributes::ITALIC),
+            4 => self.set_attributes(Attributes::UNDERLINE),
+            // Slow and rapid blink
+            5 | 6 => self.set_attributes(Attributes::BLINK),
+            7 => self.set_attributes(Attributes::REVERSE),
+            8 => self.set_attributes(Attributes::HIDDEN),
+            9 => self.set_attributes(Attributes::STRIKETHROUGH),
+            
+            // Double underline, drawn as a simple one
+            21 => self.set_attributes(Attributes::UNDERLINE),
+            // Normal intensity, neither bold nor dim
+            22 => self.reset_attributes(Attributes::BOLD | Attributes::DIM),
+            23 => self.reset_attributes(Attributes::ITALIC),
+            24 => self.reset_attributes(Attributes::UNDERLINE),
+            25 => self.reset_attributes(Attributes::BLINK),
+            27 => self.reset_attributes(Attributes::REVERSE),
+            28 => self.reset_attributes(Attributes::HIDDEN),
+            29 => self.reset_attributes(Attributes::STRIKETHROUGH),
             
             30..=37 => self.simple_color_foreground(property as u8 - 30),
             
@@ -452,3 +778,12 @@ impl Screen {
         index
     }
 }
+
+// Version of Termiku as a single number, like xterm's patch number: 0.1.0 gives 100.
+fn get_version_number() -> u32 {
+    let major: u32 = env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap();
+    let minor: u32 = env!("CARGO_PKG_VERSION_MINOR").parse().unwrap();
+    let patch: u32 = env!("CARGO_PKG_VERSION_PATCH").parse().unwrap();
+    
+    major * 10000 + minor * 100 + patch
+}
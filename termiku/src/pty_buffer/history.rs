@@ -0,0 +1,336 @@
+use super::*;
+
+// Lines which went past the top of the main screen, from the newest to the oldest.
+// Holds at most `capacity` lines, the oldest ones being dropped first.
+pub struct History {
+    lines: VecDeque<HistoryLine>,
+    capacity: usize
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            capacity
+        }
+    }
+    
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+    
+    // 0 is the newest line.
+    pub fn get(&self, index: usize) -> Option<&HistoryLine> {
+        self.lines.get(index)
+    }
+    
+    // Push a line as the newest one, and drop the oldest one if the history is full.
+    pub fn push(&mut self, line: &CellLine) {
+        if self.capacity == 0 {
+            return;
+        }
+        
+        self.lines.push_front(HistoryLine::from_cell_line(line));
+        self.lines.truncate(self.capacity);
+    }
+    
+    // Remove the newest line.
+    pub fn pop(&mut self) -> Option<HistoryLine> {
+        self.lines.pop_front()
+    }
+    
+    // Remove every line, from the oldest to the newest.
+    pub fn drain(&mut self) -> impl Iterator<Item = HistoryLine> + '_ {
+        self.lines.drain(..).rev()
+    }
+}
+
+// A line stored in the history, without any rendering data.
+// Characters are stored as a string, with the grapheme of each cell one after the other, and cell
+// properties are run-length encoded, since they usually stay the same for a lot of consecutive
+// cells.
+// Blank cells at the end of a line which doesn't wrap are not stored, and neither are the spacers
+// of wide characters, which are added back after them.
+#[derive(Clone, Debug)]
+pub struct HistoryLine {
+    characters: Box<str>,
+    // Number of cells and their properties
+    properties: Box<[(u32, CellProperties)]>,
+    pub wrapped: bool,
+    // See `CellLine::wide_padding`
+    pub wide_padding: bool
+}
+
+impl HistoryLine {
+    pub fn from_cell_line(line: &CellLine) -> Self {
+        let mut cells = &line.cells[..];
+        
+        if !line.wrapped {
+            while let Some((last, rest)) = cells.split_last() {
+                if !last.is_blank() {
+                    break;
+                }
+                
+                cells = rest;
+            }
+        }
+        
+        let mut characters = String::with_capacity(cells.len());
+        let mut properties: Vec<(u32, CellProperties)> = vec![];
+        
+        for cell in cells.iter().filter(|cell| !cell.state.is_spacer()) {
+            match &cell.state {
+                CellState::Filled(grapheme) => grapheme.push_to(&mut characters),
+                CellState::Empty | CellState::Spacer => characters.push(' '),
+                CellState::Invalid => characters.push('�')
+            }
+            
+            match properties.last_mut() {
+                Some((count, run)) if *run == cell.properties => *count += 1,
+                _ => properties.push((1, cell.properties))
+            }
+        }
+        
+        Self {
+            characters: characters.into_boxed_str(),
+            properties: properties.into_boxed_slice(),
+            wrapped: line.wrapped,
+            wide_padding: line.wide_padding
+        }
+    }
+    
+    // Cells actually stored in this line.
+    // Graphemes are split the same way they were when printed.
+    pub fn to_cells(&self) -> Vec<Cell> {
+        let mut properties = self.properties.iter()
+            .flat_map(|&(count, properties)| std::iter::repeat(properties).take(count as usize));
+        
+        let mut cells: Vec<Cell> = Vec::with_capacity(self.characters.len());
+        let mut characters = self.characters.chars().peekable();
+        
+        while let Some(character) = characters.next() {
+            let mut grapheme = Grapheme::new(character);
+            
+            while let Some(&next) = characters.peek() {
+                if !grapheme.accepts(next) {
+                    break;
+                }
+                
+                grapheme.attach(next);
+                characters.next();
+            }
+            
+            let properties = match properties.next() {
+                Some(properties) => properties,
+                None => break
+            };
+            
+            // Empty cells are stored as spaces, but a space with something attached to it was
+            // printed.
+            let state = if grapheme == Grapheme::new(' ') {
+                CellState::Empty
+            } else {
+                CellState::Filled(grapheme)
+            };
+            
+            let wide = state.is_wide();
+            
+            cells.push(Cell {
+                state,
+                properties
+            });
+            
+            if wide {
+                cells.push(Cell {
+                    state: CellState::Spacer,
+                    properties
+                });
+            }
+        }
+        
+        cells
+    }
+    
+    // Rebuild a line of `width` cells. Doesn't rasterize.
+    pub fn to_cell_line(&self, width: usize) -> CellLine {
+        let mut line = CellLine::from_cells(self.to_cells(), self.wrapped);
+        line.wide_padding = self.wide_padding;
+        line.resize(width);
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Wide characters take two cells.
+    fn line(text: &str, properties: CellProperties, wrapped: bool) -> CellLine {
+        let mut cells: Vec<Cell> = vec![];
+        
+        for character in text.chars() {
+            let state = if character == ' ' { CellState::Empty } else { CellState::Filled(Grapheme::new(character)) };
+            let wide = state.is_wide();
+            
+            cells.push(Cell {
+                state,
+                properties
+            });
+            
+            if wide {
+                cells.push(Cell {
+                    state: CellState::Spacer,
+                    properties
+                });
+            }
+        }
+        
+        CellLine::from_cells(cells, wrapped)
+    }
+    
+    // Characters of the cells, with spaces for empty ones and without spacers.
+    fn text(cells: &[Cell]) -> String {
+        cells.iter()
+            .filter_map(|cell| match &cell.state {
+                CellState::Filled(grapheme) => Some(grapheme.base()),
+                CellState::Empty => Some(' '),
+                CellState::Invalid => Some('�'),
+                CellState::Spacer => None
+            })
+            .collect()
+    }
+    
+    // Graphemes of the cells, with empty strings for the cells which aren't filled.
+    fn graphemes(cells: &[Cell]) -> Vec<String> {
+        cells.iter()
+            .map(|cell| {
+                let mut text = String::new();
+                
+                if let CellState::Filled(grapheme) = &cell.state {
+                    grapheme.push_to(&mut text);
+                }
+                
+                text
+            })
+            .collect()
+    }
+    
+    #[test]
+    fn lines_keep_their_characters_and_properties() {
+        let red = CellProperties {
+            fg: Color::from_rgb(255, 0, 0),
+            ..CellProperties::new()
+        };
+        
+        let mut cells = line("ab c", red, false).cells;
+        cells[2].properties = CellProperties::new();
+        
+        let history_line = HistoryLine::from_cell_line(&CellLine::from_cells(cells.clone(), false));
+        let restored = history_line.to_cell_line(6);
+        
+        assert_eq!(text(&restored.cells), "ab c  ");
+        
+        for (restored, original) in restored.cells.iter().zip(&cells) {
+            assert_eq!(restored.properties, original.properties);
+        }
+    }
+    
+    #[test]
+    fn only_blank_cells_ending_a_line_which_does_not_wrap_are_dropped() {
+        let blue_background = CellProperties {
+            bg: Some(Color::from_rgb(0, 0, 255)),
+            ..CellProperties::new()
+        };
+        
+        assert_eq!(HistoryLine::from_cell_line(&line("ab  ", CellProperties::new(), false)).to_cells().len(), 2);
+        assert_eq!(HistoryLine::from_cell_line(&line("ab  ", CellProperties::new(), true)).to_cells().len(), 4);
+        assert_eq!(HistoryLine::from_cell_line(&line("ab  ", blue_background, false)).to_cells().len(), 4);
+    }
+    
+    #[test]
+    fn wide_characters_get_their_spacer_back() {
+        let cells = HistoryLine::from_cell_line(&line("a漢b", CellProperties::new(), false)).to_cells();
+        
+        assert_eq!(text(&cells), "a漢b");
+        assert_eq!(cells.len(), 4);
+        assert!(cells[1].state.is_wide() && cells[2].state.is_spacer());
+    }
+    
+    #[test]
+    fn graphemes_keep_their_attached_characters() {
+        let mut cells = line("e👩x", CellProperties::new(), false).cells;
+        
+        if let CellState::Filled(grapheme) = &mut cells[0].state {
+            grapheme.attach('\u{301}');
+        }
+        
+        if let CellState::Filled(grapheme) = &mut cells[1].state {
+            grapheme.attach('\u{200D}');
+            grapheme.attach('💻');
+        }
+        
+        let restored = HistoryLine::from_cell_line(&CellLine::from_cells(cells, false)).to_cells();
+        
+        assert_eq!(graphemes(&restored), vec!["e\u{301}", "👩\u{200D}💻", "", "x"]);
+    }
+    
+    #[test]
+    fn lines_come_back_cell_for_cell() {
+        let blue_background = CellProperties {
+            bg: Some(Color::from_rgb(0, 0, 255)),
+            ..CellProperties::new()
+        };
+        
+        // A space with a mark, a wide character, blue blank cells, then a character
+        let mut cells = line(" 漢", CellProperties::new(), false).cells;
+        let mut space = Grapheme::new(' ');
+        space.attach('\u{301}');
+        cells[0].state = CellState::Filled(space);
+        cells.extend(line("   ", blue_background, false).cells);
+        cells.extend(line("x", CellProperties::new(), false).cells);
+        
+        let restored = HistoryLine::from_cell_line(&CellLine::from_cells(cells.clone(), false)).to_cells();
+        
+        assert_eq!(graphemes(&restored), vec![" \u{301}", "漢", "", "", "", "", "x"]);
+        assert!(restored[2].state.is_spacer());
+        
+        for cell in &restored[3..6] {
+            match cell.state {
+                CellState::Empty => {},
+                _ => panic!("blank cell restored as {:?}", cell.state)
+            }
+        }
+        
+        for (restored, original) in restored.iter().zip(&cells) {
+            assert_eq!(restored.properties, original.properties);
+        }
+    }
+    
+    #[test]
+    fn oldest_lines_are_dropped_first() {
+        let mut history = History::new(2);
+        
+        for content in &["a", "b", "c"] {
+            history.push(&line(content, CellProperties::new(), false));
+        }
+        
+        assert_eq!(history.len(), 2);
+        assert_eq!(text(&history.get(0).unwrap().to_cells()), "c");
+        assert_eq!(text(&history.get(1).unwrap().to_cells()), "b");
+        
+        assert_eq!(text(&history.pop().unwrap().to_cells()), "c");
+        assert_eq!(history.len(), 1);
+    }
+    
+    #[test]
+    fn nothing_is_kept_without_capacity() {
+        let mut history = History::new(0);
+        history.push(&line("a", CellProperties::new(), false));
+        
+        assert!(history.is_empty());
+    }
+}
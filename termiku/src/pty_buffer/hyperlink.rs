@@ -0,0 +1,108 @@
+use super::*;
+
+// Number of hyperlinks kept by a screen. Once reached, the oldest ones are dropped, and the cells
+// still referring to them, usually far in the history by then, lose their link.
+const HYPERLINK_TABLE_SIZE: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hyperlink {
+    // Set by the program to join the parts of a link, like the lines of a link split by a pane.
+    pub id: Option<String>,
+    pub uri: String
+}
+
+// Links set by OSC 8, which cells refer to by id. Ids only ever increase, so that a dropped link
+// isn't mistaken for a newer one.
+pub struct HyperlinkTable {
+    links: VecDeque<Hyperlink>,
+    // Id of the oldest link kept
+    first_id: u32
+}
+
+impl HyperlinkTable {
+    pub fn new() -> Self {
+        Self {
+            links: VecDeque::new(),
+            first_id: 0
+        }
+    }
+    
+    pub fn get(&self, id: u32) -> Option<&Hyperlink> {
+        self.links.get(id.checked_sub(self.first_id)? as usize)
+    }
+    
+    // Id of a new link, or of an existing one with the same id and URI. Links without an id are
+    // only joined to the newest one, as when a program sets the same link again for each line.
+    pub fn insert(&mut self, link: Hyperlink) -> u32 {
+        let existing = if link.id.is_some() {
+            self.links.iter().rposition(|existing| *existing == link)
+        } else if self.links.back() == Some(&link) {
+            Some(self.links.len() - 1)
+        } else {
+            None
+        };
+        
+        if let Some(index) = existing {
+            return self.first_id + index as u32;
+        }
+        
+        if self.links.len() == HYPERLINK_TABLE_SIZE {
+            self.links.pop_front();
+            self.first_id += 1;
+        }
+        
+        self.links.push_back(link);
+        self.first_id + self.links.len() as u32 - 1
+    }
+}
+
+impl Screen {
+    // An empty URI ends the current link.
+    pub fn set_hyperlink(&mut self, id: Option<String>, uri: String) {
+        self.cursor.properties.hyperlink = if uri.is_empty() {
+            None
+        } else {
+            Some(self.hyperlinks.insert(Hyperlink { id, uri }))
+        };
+    }
+    
+    pub fn get_hyperlink(&self, id: u32) -> Option<&Hyperlink> {
+        self.hyperlinks.get(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn link(id: Option<&str>, uri: &str) -> Hyperlink {
+        Hyperlink {
+            id: id.map(String::from),
+            uri: uri.to_string()
+        }
+    }
+    
+    #[test]
+    fn links_with_an_id_are_joined_to_any_matching_one() {
+        let mut table = HyperlinkTable::new();
+        
+        let first = table.insert(link(Some("a"), "https://a"));
+        table.insert(link(Some("b"), "https://b"));
+        
+        assert_eq!(table.insert(link(Some("a"), "https://a")), first);
+        assert_ne!(table.insert(link(Some("a"), "https://c")), first);
+    }
+    
+    #[test]
+    fn links_without_an_id_are_only_joined_to_the_newest_one() {
+        let mut table = HyperlinkTable::new();
+        
+        let first = table.insert(link(None, "https://a"));
+        
+        assert_eq!(table.insert(link(None, "https://a")), first);
+        
+        table.insert(link(None, "https://b"));
+        
+        assert_ne!(table.insert(link(None, "https://a")), first);
+    }
+}
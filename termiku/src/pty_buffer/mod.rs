@@ -1,34 +1,58 @@
+mod charset;
 mod handle_control_sequence;
+mod history;
+mod hyperlink;
+mod reflow;
+mod selection;
 
 pub mod event;
 pub mod sgr;
 
-use std::collections::VecDeque;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 
+use unicode_width::UnicodeWidthChar;
+
 use crate::atlas::RectSize;
+use crate::config::Config;
 use crate::control::*;
 use crate::rasterizer::*;
 use crate::utf8::*;
+use crate::window_event::ScrollView;
 
+use charset::*;
 use event::*;
+use history::*;
+use hyperlink::*;
+use reflow::*;
+
+pub use selection::SelectionKind;
+use selection::*;
 
 const BELL_BYTE: u8 = 0x07;
 const BACKSPACE_BYTE: u8 = 0x08;
 const TABULATION_BYTE: u8 = 0x09;
 const LINE_FEED_BYTE: u8 = 0x0A;
 const CARRIAGE_RETURN_BYTE: u8 = 0x0D;
+const SHIFT_OUT_BYTE: u8 = 0x0E;
+const SHIFT_IN_BYTE: u8 = 0x0F;
 
 fn is_special_byte(byte: u8) -> bool {
     byte == BELL_BYTE ||
     byte == BACKSPACE_BYTE ||
     byte == TABULATION_BYTE ||
     byte == LINE_FEED_BYTE ||
-    byte == CARRIAGE_RETURN_BYTE
+    byte == CARRIAGE_RETURN_BYTE ||
+    byte == SHIFT_OUT_BYTE ||
+    byte == SHIFT_IN_BYTE
 }
 
 const TAB_LENGTH: usize = 8;
 
+// Same limit as xterm's.
+const TITLE_STACK_SIZE: usize = 10;
+
 // Cursor positions
 // They are 1 based
 // They start from the top left
@@ -45,12 +69,17 @@ impl Position {
             y: 1
         }
     }
+
+    pub fn clamp(&mut self, width: usize, height: usize) {
+        self.x = self.x.min(width).max(1);
+        self.y = self.y.min(height).max(1);
+    }
 }
 
 // R G B A
 // Black is 0,0,0
 // White is 255, 255, 255
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 // FIXME: pty_buffer::Color should have named fields instead of being a tuple struct.
 pub struct Color(pub u8, pub u8, pub u8, pub u8);
 
@@ -80,10 +109,58 @@ impl Color {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+// Set of the attributes set by SGR, one bit per attribute.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Attributes(u16);
+
+impl Attributes {
+    pub const BOLD: Self = Self(1);
+    pub const DIM: Self = Self(1 << 1);
+    pub const ITALIC: Self = Self(1 << 2);
+    pub const UNDERLINE: Self = Self(1 << 3);
+    pub const BLINK: Self = Self(1 << 4);
+    pub const REVERSE: Self = Self(1 << 5);
+    pub const HIDDEN: Self = Self(1 << 6);
+    pub const STRIKETHROUGH: Self = Self(1 << 7);
+    
+    pub fn empty() -> Self {
+        Self(0)
+    }
+    
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+    
+    // Whether all the attributes of `other` are set.
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+    
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+    
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+impl std::ops::BitOr for Attributes {
+    type Output = Self;
+    
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct CellProperties {
     pub fg: Color,
-    pub bg: Option<Color>
+    pub bg: Option<Color>,
+    pub attributes: Attributes,
+    // Id of the link of the cell in the hyperlink table of its screen, kept in the history along
+    // with the other properties
+    pub hyperlink: Option<u32>
 }
 
 // Should probably need a Config from somewhere
@@ -91,11 +168,23 @@ impl CellProperties {
     pub fn new() -> Self {
         Self {
             fg: DEFAULT_FG,
-            bg: None
+            bg: None,
+            attributes: Attributes::empty(),
+            hyperlink: None
         }
     }
 }
 
+// What DECSC saves along with the position of the cursor, and DECRC restores
+#[derive(Copy, Clone, Debug)]
+pub struct SavedCursorState {
+    properties: CellProperties,
+    charsets: Charsets,
+    origin_mode: bool,
+    auto_wrap: bool,
+    wrap_pending: bool
+}
+
 // Cursor can hold its current position and its saved position, if it exists
 // Also holds the cell properties of the next cells to create (fg and gb colors, bold, italic, etc)
 // It's different than the cursor displayed on screen, and therefore should not hold any
@@ -103,7 +192,12 @@ impl CellProperties {
 #[derive(Copy, Clone, Debug)]
 pub struct Cursor {
     position: Position,
+    // Whether a character was just printed in the last column, in which case the cursor stays on
+    // it until the next printed character goes to the next line with DECAWM, or replaces it
+    // without. Any movement of the cursor cancels it.
+    wrap_pending: bool,
     saved: Option<Position>,
+    saved_state: Option<SavedCursorState>,
     properties: CellProperties,
 }
 
@@ -111,7 +205,9 @@ impl Cursor {
     pub fn new() -> Self {
         Self {
             position: Position::new(),
+            wrap_pending: false,
             saved: None,
+            saved_state: None,
             properties: CellProperties::new()
         }
     }
@@ -123,6 +219,66 @@ impl Cursor {
     pub fn restore(&mut self) {
         if let Some(position) = &self.saved {
             self.position = *position;
+            self.wrap_pending = false;
+        }
+    }
+
+    // Make sure both the current and the saved positions fit inside a screen of the given size.
+    // A wrap pending on a column which isn't the last one anymore is done by moving the cursor
+    // after the character it was left on.
+    pub fn clamp(&mut self, width: usize, height: usize) {
+        if self.wrap_pending && self.position.x < width {
+            self.position.x += 1;
+            self.wrap_pending = false;
+        }
+        
+        self.position.clamp(width, height);
+
+        if let Some(saved) = &mut self.saved {
+            saved.clamp(width, height);
+        }
+    }
+}
+
+// Shapes of the cursor set by DECSCUSR
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+    Bar
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool
+}
+
+impl CursorStyle {
+    // Parameter of DECSCUSR, with 0 and 1 both being the default blinking block.
+    pub fn from_parameter(parameter: u16) -> Option<Self> {
+        let (shape, blinking) = match parameter {
+            0 | 1 => (CursorShape::Block, true),
+            2 => (CursorShape::Block, false),
+            3 => (CursorShape::Underline, true),
+            4 => (CursorShape::Underline, false),
+            5 => (CursorShape::Bar, true),
+            6 => (CursorShape::Bar, false),
+            _ => return None
+        };
+        
+        Some(Self {
+            shape,
+            blinking
+        })
+    }
+}
+
+impl Default for CursorStyle {
+    fn default() -> Self {
+        Self {
+            shape: CursorShape::Block,
+            blinking: true
         }
     }
 }
@@ -147,46 +303,87 @@ impl CharacterGroup {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub enum CellState {
-    Empty,
-    Filling(UTF8Decoder),
-    Filled(char),
-    Invalid
+// Whether a character takes two cells on screen, as defined by its Unicode East Asian Width.
+pub fn is_wide_character(character: char) -> bool {
+    character.width() == Some(2)
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+// Content of a filled cell: a character, followed by the zero width characters attached to it,
+// like combining marks, variation selectors or the rest of an emoji sequence.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grapheme {
+    base: char,
+    // Most characters don't have anything attached to them, so they don't need an allocation.
+    attached: Option<Box<str>>
 }
 
-impl CellState {    
-    fn get_start(first_byte: u8) -> Self {
-        let parser = UTF8Decoder::new();
-        Self::get_cell_from_parser_and_byte(parser, first_byte)
+impl Grapheme {
+    pub fn new(base: char) -> Self {
+        Self {
+            base,
+            attached: None
+        }
     }
     
-    // FIXME: pty_buffer::CellState::get_cell_from_parser_and_byte() probably shouldn't be marked inline...
-    #[inline]
-    fn get_cell_from_parser_and_byte(mut decoder: UTF8Decoder, byte: u8) -> CellState {
-        match decoder.decode_byte(byte) {
-            DecodeState::Continue => CellState::Filling(decoder),
-            DecodeState::Done(c) => CellState::Filled(c),
-            DecodeState::Rewind => {
-                match decoder.decode_byte(byte) {
-                    DecodeState::Continue => CellState::Filling(decoder),
-                    DecodeState::Done(c)  => CellState::Filled(c),
-                    _                     => CellState::Invalid
-                }
-            },
-            DecodeState::Error => CellState::Invalid
+    pub fn base(&self) -> char {
+        self.base
+    }
+    
+    // Whether a character following this grapheme is part of it, instead of starting a new one.
+    pub fn accepts(&self, character: char) -> bool {
+        let last = self.attached.as_ref()
+            .and_then(|attached| attached.chars().last())
+            .unwrap_or(self.base);
+        
+        last == ZERO_WIDTH_JOINER || (!character.is_control() && character.width() == Some(0))
+    }
+    
+    pub fn attach(&mut self, character: char) {
+        let mut attached = self.attached.take().map(String::from).unwrap_or_default();
+        attached.push(character);
+        
+        self.attached = Some(attached.into_boxed_str());
+    }
+    
+    pub fn push_to(&self, string: &mut String) {
+        string.push(self.base);
+        
+        if let Some(attached) = &self.attached {
+            string.push_str(attached);
+        }
+    }
+}
+
+// A wide character is held by its left cell, and its right cell is a spacer.
+// The cursor moves over both cells like over any other ones. Printing or erasing on one half of a
+// wide character erases the other half as well.
+#[derive(Clone, Debug)]
+pub enum CellState {
+    Empty,
+    Filled(Grapheme),
+    Invalid,
+    Spacer
+}
+
+impl CellState {
+    pub fn is_wide(&self) -> bool {
+        match self {
+            CellState::Filled(grapheme) => is_wide_character(grapheme.base()),
+            _ => false
         }
     }
     
-    pub fn next_state(&mut self, new_byte: u8) -> CellState {
+    pub fn is_spacer(&self) -> bool {
         match self {
-            CellState::Filled(_) | CellState::Invalid  | CellState::Empty => CellState::get_start(new_byte),
-            CellState::Filling(parser) => Self::get_cell_from_parser_and_byte(*parser, new_byte)
+            CellState::Spacer => true,
+            _ => false
         }
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Cell {
     pub state: CellState,
     pub properties: CellProperties
@@ -199,50 +396,254 @@ impl Cell {
             state: CellState::Empty
         }
     }
+    
+    // An empty cell that isn't painted with a background color or an attribute either.
+    pub fn is_blank(&self) -> bool {
+        match self.state {
+            CellState::Empty => self.properties.bg.is_none() && self.properties.attributes.is_empty(),
+            _ => false
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct CellLine {
     pub cells: Vec<Cell>,
-    pub display: Vec<DisplayCellLine>
+    pub display: Vec<DisplayCellLine>,
+    /// Whether this line was filled up to the last column and then continued on the next line
+    /// (soft wrap), as opposed to being ended by a line feed or a cursor movement.
+    pub wrapped: bool,
+    /// Whether the last cell was left empty because a wide character didn't fit in it and went
+    /// on the next line instead, in which case it isn't part of the content of a wrapped line.
+    pub wide_padding: bool
 }
 
 impl CellLine {
     pub fn new(width: usize, properties: CellProperties) -> Self {
+        Self::from_cells(vec![Cell::empty(properties); width], false)
+    }
+    
+    // Doesn't rasterize.
+    pub fn from_cells(cells: Vec<Cell>, wrapped: bool) -> Self {
         Self {
-            cells: vec![Cell::empty(properties); width],
-            display: vec![DisplayCellLine::empty()]
+            cells,
+            display: vec![DisplayCellLine::empty()],
+            wrapped,
+            wide_padding: false
         }
     }
     
     pub fn rasterize(&mut self, rasterizer: &mut Rasterizer) {
         self.display = rasterizer.cells_to_display_cell_lines(&self.cells);
     }
+
+    // Truncate the line or extend it with empty cells. Doesn't rasterize.
+    pub fn resize(&mut self, width: usize) {
+        self.cells.resize(width, Cell::empty(CellProperties::new()));
+        
+        // Truncating may have split the last wide character.
+        self.fix_wide_characters(width.saturating_sub(1), width);
+    }
+    
+    // Erase the wide characters missing one of their halves, around the cells between start and
+    // end (0 based, exclusive). Doesn't rasterize.
+    pub fn fix_wide_characters(&mut self, start: usize, end: usize) {
+        let start = start.saturating_sub(1);
+        let end = (end + 1).min(self.cells.len());
+        
+        for column in start..end {
+            let state = &self.cells[column].state;
+            
+            let broken = if state.is_spacer() {
+                column == 0 || !self.cells[column - 1].state.is_wide()
+            } else if state.is_wide() {
+                column + 1 >= self.cells.len() || !self.cells[column + 1].state.is_spacer()
+            } else {
+                false
+            };
+            
+            if broken {
+                self.cells[column].state = CellState::Empty;
+            }
+        }
+    }
+    
+    // Index of the display cell showing a column, since spacers don't have their own display
+    // cell. A spacer gives the display cell of its wide character.
+    pub fn get_display_index(&self, column: usize) -> usize {
+        let end = (column + 1).min(self.cells.len());
+        
+        self.cells[..end].iter()
+            .filter(|cell| !cell.state.is_spacer())
+            .count()
+            .saturating_sub(1)
+    }
+    
+    // Erase the cells between start and end (0 based, exclusive). Doesn't rasterize.
+    pub fn erase_cells(&mut self, start: usize, end: usize, properties: CellProperties) {
+        let end = end.min(self.cells.len());
+        
+        if start < end {
+            for cell in &mut self.cells[start..end] {
+                *cell = Cell::empty(properties);
+            }
+            
+            self.fix_wide_characters(start, end);
+        }
+    }
+    
+    // Add blank cells at start (0 based), pushing the cells after it to the right. Cells going
+    // past the end of the line are lost. Doesn't rasterize.
+    pub fn insert_cells(&mut self, start: usize, count: usize, properties: CellProperties) {
+        let width = self.cells.len();
+        let count = count.min(width - start);
+        
+        self.cells.truncate(width - count);
+        self.cells.splice(start..start, std::iter::repeat(Cell::empty(properties)).take(count));
+        self.fix_wide_characters(0, width);
+    }
+    
+    // Remove cells from start (0 based), pulling the cells after it to the left and adding blank
+    // cells at the end of the line. Doesn't rasterize.
+    pub fn delete_cells(&mut self, start: usize, count: usize, properties: CellProperties) {
+        let width = self.cells.len();
+        let count = count.min(width - start);
+        
+        self.cells.drain(start..start + count);
+        self.cells.resize(width, Cell::empty(properties));
+        self.fix_wide_characters(0, width);
+    }
+    
+    // A line without any content, which can safely be dropped when reflowing.
+    pub fn is_blank(&self) -> bool {
+        !self.wrapped && self.cells.iter().all(Cell::is_blank)
+    }
+}
+
+// Lines between top and bottom (1 based, inclusive) scroll together when the cursor goes past
+// them, while the ones outside stay in place.
+#[derive(Copy, Clone, Debug)]
+pub struct ScrollRegion {
+    pub top: usize,
+    pub bottom: usize
+}
+
+impl ScrollRegion {
+    pub fn full(height: usize) -> Self {
+        Self {
+            top: 1,
+            bottom: height
+        }
+    }
+    
+    pub fn is_full(&self, height: usize) -> bool {
+        self.top == 1 && self.bottom == height
+    }
 }
 
-#[derive(Copy, Clone, Default)]
+// Mouse events reported to the program, from the least to the most
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MouseTracking {
+    None,
+    /// 1000, presses, releases and the wheel
+    Normal,
+    /// 1002, and motion while a button is held
+    ButtonEvent,
+    /// 1003, and any motion
+    AnyEvent
+}
+
+// How the mouse events are encoded
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MouseEncoding {
+    /// Bytes offset by 32, up to the 223rd row or column
+    X10,
+    /// 1005, coordinates encoded as UTF-8 characters
+    Utf8,
+    /// 1006, parameters in decimal, with releases ending with 'm'
+    Sgr,
+    /// 1015, parameters in decimal
+    Urxvt
+}
+
+#[derive(Copy, Clone)]
 pub struct ScreenState {
     /// Alternative buffer state
-    pub is_alternative: bool
+    pub is_alternative: bool,
+    /// Whether the keypad sends application sequences instead of its characters
+    pub application_keypad: bool,
+    /// Character sets used for the printed characters
+    pub charsets: Charsets,
+    /// IRM, whether printed characters shift the ones after them instead of replacing them
+    pub insert_mode: bool,
+    /// LNM, whether line feeds also go back to the first column
+    pub new_line_mode: bool,
+    /// DECCKM, whether the cursor keys send application sequences
+    pub application_cursor: bool,
+    /// DECOM, whether the cursor positions are relative to the scroll region
+    pub origin_mode: bool,
+    /// DECAWM, whether printing past the last column goes to the next line
+    pub auto_wrap: bool,
+    /// DECTCEM, whether the cursor is shown
+    pub cursor_visible: bool,
+    /// Shape of the cursor, and whether it blinks
+    pub cursor_style: CursorStyle,
+    /// Mouse events reported to the program
+    pub mouse_tracking: MouseTracking,
+    pub mouse_encoding: MouseEncoding,
+    /// Whether pasted text is enclosed in ESC[200~ and ESC[201~
+    pub bracketed_paste: bool
+}
+
+impl Default for ScreenState {
+    fn default() -> Self {
+        Self {
+            is_alternative: false,
+            application_keypad: false,
+            charsets: Charsets::default(),
+            insert_mode: false,
+            new_line_mode: false,
+            application_cursor: false,
+            origin_mode: false,
+            auto_wrap: true,
+            cursor_visible: true,
+            cursor_style: CursorStyle::default(),
+            mouse_tracking: MouseTracking::None,
+            mouse_encoding: MouseEncoding::X10,
+            bracketed_paste: false
+        }
+    }
 }
 
 pub struct Screen {
     pub line_cell_width: usize,
     pub line_cell_height: usize,
-    pub history: VecDeque<CellLine>,
+    pub history: History,
     pub control_parser: ControlSequenceParser,
+    // Characters printed on screen can be split across several reads of the pty.
+    pub decoder: UTF8Decoder,
     pub screen_lines: Vec<CellLine>,
     pub cursor: Cursor,
     pub alternative_screen_lines: Vec<CellLine>,
-    // FIXME Screen::alternative_cursor can probably be removed. The alternate screen switching should save/restore the cursor.
+    // The cursor is shared by both screens, this is its copy for the screen not shown, used to
+    // resize it, and holding the saved position of the main screen while it's hidden.
     pub alternative_cursor: Cursor,
+    pub scroll_region: ScrollRegion,
     pub state: ScreenState,
     pub sender: Arc<Mutex<mio_extras::channel::Sender<ScreenEvent>>>,
-    pub id: usize
+    pub id: usize,
+    // Title set by the programs, and the ones they saved to restore them later.
+    pub title: String,
+    pub title_stack: Vec<String>,
+    // Links of the cells of both screens and of the history
+    pub hyperlinks: HyperlinkTable,
+    /// Number of lines pushed to the history by new output since the creation of the screen, and
+    /// moved to it or taken back from it when resizing.
+    pub pushed_to_history: usize
 }
 
 impl Screen {
-    pub fn empty(sender: mio_extras::channel::Sender<ScreenEvent>, rasterizer: &mut Rasterizer, id: usize) -> Self {
+    pub fn empty(sender: mio_extras::channel::Sender<ScreenEvent>, rasterizer: &mut Rasterizer, id: usize, history_size: usize) -> Self {
         let line_cell_size = rasterizer.get_line_cell_size();
         
         let line_cell_width = line_cell_size.width as usize;
@@ -253,7 +654,7 @@ impl Screen {
             line.rasterize(rasterizer)
         }
         
-        let history: VecDeque<CellLine> = VecDeque::new();
+        let history = History::new(history_size);
         
         let cursor = Cursor::new();
         
@@ -262,6 +663,7 @@ impl Screen {
             line_cell_height,
             history,
             control_parser: ControlSequenceParser::new(),
+            decoder: UTF8Decoder::new(),
             
             screen_lines: screen_lines.clone(),
             cursor,
@@ -269,16 +671,173 @@ impl Screen {
             alternative_screen_lines: screen_lines,
             alternative_cursor: cursor,
             
+            scroll_region: ScrollRegion::full(line_cell_height),
             state: ScreenState::default(),
             sender: Arc::new(Mutex::new(sender)),
-            id
+            id,
+            title: String::new(),
+            title_stack: vec![],
+            hyperlinks: HyperlinkTable::new(),
+            pushed_to_history: 0
         }
     }
     
-    // FIXME: Can Screen::update_line_cell_dimensions be removed?
-    pub fn update_line_cell_dimensions(&mut self, _line_cell_size: RectSize) {
-        // self.line_cell_height = line_cell_size.height as usize;
-        // self.line_cell_width = line_cell_size.width as usize;
+    // Resize both the main and the alternative screens to the new number of rows and columns.
+    // When the number of columns changes, the main screen and its history are rewrapped (see
+    // reflow.rs), while the lines of the alternative screen are simply truncated or extended.
+    // When the main screen loses rows, the ones overflowing at the top go to the history, and
+    // when it gains rows, they are taken back from the history if there are any.
+    pub fn update_line_cell_dimensions(&mut self, line_cell_size: RectSize, rasterizer: &mut Rasterizer) {
+        let width = line_cell_size.width as usize;
+        let height = line_cell_size.height as usize;
+
+        if width == self.line_cell_width && height == self.line_cell_height {
+            return;
+        }
+
+        let width_changed = width != self.line_cell_width;
+        
+        self.line_cell_width = width;
+        self.line_cell_height = height;
+        self.scroll_region = ScrollRegion::full(height);
+
+        // Screens are swapped when using the alternative buffer, so the main screen isn't always
+        // the one inside `screen_lines`.
+        let (main_lines, main_cursor, alternative_lines, alternative_cursor) = if self.state.is_alternative {
+            (&mut self.alternative_screen_lines, &mut self.alternative_cursor, &mut self.screen_lines, &mut self.cursor)
+        } else {
+            (&mut self.screen_lines, &mut self.cursor, &mut self.alternative_screen_lines, &mut self.alternative_cursor)
+        };
+
+        let moved = if width_changed {
+            reflow_lines(main_lines, main_cursor, &mut self.history, width, height)
+        } else {
+            resize_lines(main_lines, main_cursor, Some(&mut self.history), width, height)
+        };
+        resize_lines(alternative_lines, alternative_cursor, None, width, height);
+
+        // Grid lines count from the top of the screen, which moved with the lines going to or
+        // coming from the history.
+        self.pushed_to_history = self.pushed_to_history.wrapping_add(moved as usize);
+        
+        self.rasterize_lines(rasterizer);
+    }
+    
+    pub fn rasterize_lines(&mut self, rasterizer: &mut Rasterizer) {
+        for line in self.screen_lines.iter_mut().chain(self.alternative_screen_lines.iter_mut()) {
+            line.rasterize(rasterizer);
+        }
+    }
+    
+    // Bring the screen back to the state it had when created. The history, the links its cells
+    // refer to, and the title are kept.
+    pub fn reset(&mut self, rasterizer: &mut Rasterizer) {
+        let mut screen_lines = vec![CellLine::new(self.line_cell_width, CellProperties::new()); self.line_cell_height];
+        
+        for line in screen_lines.iter_mut() {
+            line.rasterize(rasterizer);
+        }
+        
+        self.screen_lines = screen_lines.clone();
+        self.alternative_screen_lines = screen_lines;
+        self.cursor = Cursor::new();
+        self.alternative_cursor = Cursor::new();
+        self.scroll_region = ScrollRegion::full(self.line_cell_height);
+        self.state = ScreenState::default();
+        self.decoder = UTF8Decoder::new();
+        self.title_stack.clear();
+    }
+    
+    // Show the alternative screen, which has no history, optionally clearing it.
+    pub fn enter_alternative_screen(&mut self, clear: bool, rasterizer: &mut Rasterizer) {
+        if self.state.is_alternative {
+            return;
+        }
+        
+        self.state.is_alternative = true;
+        self.alternative_cursor = self.cursor;
+        std::mem::swap(&mut self.screen_lines, &mut self.alternative_screen_lines);
+        
+        if clear {
+            self.clear_screen(rasterizer);
+        }
+    }
+    
+    // Show the main screen again, optionally clearing the alternative screen beforehand.
+    pub fn leave_alternative_screen(&mut self, clear: bool, rasterizer: &mut Rasterizer) {
+        if !self.state.is_alternative {
+            return;
+        }
+        
+        if clear {
+            self.clear_screen(rasterizer);
+        }
+        
+        self.state.is_alternative = false;
+        
+        // The saved position of the main screen was rewrapped along with it when resized, so it's
+        // taken from its copy of the cursor.
+        let main_cursor = std::mem::replace(&mut self.alternative_cursor, self.cursor);
+        self.cursor.saved = main_cursor.saved;
+        self.cursor.saved_state = main_cursor.saved_state;
+        
+        std::mem::swap(&mut self.screen_lines, &mut self.alternative_screen_lines);
+    }
+    
+    fn clear_screen(&mut self, rasterizer: &mut Rasterizer) {
+        let line = self.erased_line(rasterizer);
+        
+        for screen_line in self.screen_lines.iter_mut() {
+            *screen_line = line.clone();
+        }
+    }
+    
+    // DECSC: save the position of the cursor, along with the properties of the cells to print,
+    // the character sets, DECOM, DECAWM and a pending wrap.
+    // Positions are saved from the top of the screen, so they don't depend on the scroll region.
+    pub fn save_cursor(&mut self) {
+        self.cursor.save();
+        self.cursor.saved_state = Some(SavedCursorState {
+            properties: self.cursor.properties,
+            charsets: self.state.charsets,
+            origin_mode: self.state.origin_mode,
+            auto_wrap: self.state.auto_wrap,
+            wrap_pending: self.cursor.wrap_pending
+        });
+    }
+    
+    // DECRC
+    pub fn restore_cursor(&mut self) {
+        self.cursor.restore();
+        
+        if let Some(saved_state) = self.cursor.saved_state {
+            self.cursor.properties = saved_state.properties;
+            // The saved position may not be on the last column anymore after a resize.
+            self.cursor.wrap_pending = saved_state.wrap_pending && self.cursor.position.x == self.line_cell_width;
+            self.state.charsets = saved_state.charsets;
+            self.state.origin_mode = saved_state.origin_mode;
+            self.state.auto_wrap = saved_state.auto_wrap;
+        }
+    }
+    
+    // Move the cursor to the first column of the first line, or of the scroll region with DECOM.
+    pub fn home_cursor(&mut self) {
+        self.cursor.wrap_pending = false;
+        self.cursor.position.x = 1;
+        self.cursor.position.y = if self.state.origin_mode {
+            self.scroll_region.top
+        } else {
+            1
+        };
+    }
+    
+    // Move the cursor one line down, and to the first column with LNM.
+    pub fn line_feed(&mut self, rasterizer: &mut Rasterizer) {
+        self.next_line(rasterizer);
+        
+        if self.state.new_line_mode {
+            self.cursor.position.x = 1;
+        }
     }
     
     pub fn add_to_buffer(&mut self, data: &[u8], rasterizer: &mut Rasterizer) {
@@ -313,14 +872,30 @@ impl Screen {
         }
     }
 
+    // Move the cursor one line down, scrolling the scroll region up if the cursor is at its
+    // bottom. Below the scroll region, the cursor stops at the last line.
     pub fn next_line(&mut self, rasterizer: &mut Rasterizer) {
-        if self.cursor.position.y == self.line_cell_height {
-            self.push_line_to_history(rasterizer);
-        } else {
+        self.cursor.wrap_pending = false;
+        
+        if self.cursor.position.y == self.scroll_region.bottom {
+            self.scroll_up(1, rasterizer);
+        } else if self.cursor.position.y < self.line_cell_height {
             self.cursor.position.y += 1;
         }
     }
     
+    // Move the cursor one line up, scrolling the scroll region down if the cursor is at its
+    // top. Above the scroll region, the cursor stops at the first line.
+    pub fn previous_line(&mut self, rasterizer: &mut Rasterizer) {
+        self.cursor.wrap_pending = false;
+        
+        if self.cursor.position.y == self.scroll_region.top {
+            self.scroll_down(1, rasterizer);
+        } else if self.cursor.position.y > 1 {
+            self.cursor.position.y -= 1;
+        }
+    }
+    
     fn get_position_pointed_by_cursor(&self) -> (usize, usize) {
         let mut row_number = self.cursor.position.y - 1;
         if row_number >= self.line_cell_height {
@@ -353,6 +928,8 @@ impl Screen {
                 if column_number != 0 {
                     self.cursor.position.x -= 1;
                 }
+                
+                self.cursor.wrap_pending = false;
             },
             
             TABULATION_BYTE => {
@@ -366,14 +943,28 @@ impl Screen {
                 }
                 
                 self.cursor.position.x = new_column;
+                self.cursor.wrap_pending = false;
+            },
+            
+            LINE_FEED_BYTE => {
+                self.line_feed(rasterizer);
             },
             
             CARRIAGE_RETURN_BYTE => {
                 self.cursor.position.x = 1;
-            }
+                self.cursor.wrap_pending = false;
+            },
+            
+            // Invoke G1 or G0 for the next printed characters.
+            SHIFT_OUT_BYTE => {
+                self.state.charsets.invoke(1);
+            },
+            
+            SHIFT_IN_BYTE => {
+                self.state.charsets.invoke(0);
+            },
             
-            // Should never reach a line feed for now (handled upper in the stack), so i want to
-            // crash if we somehow do encouter it.
+            // Only the bytes of is_special_byte get here.
             _ => unreachable!()
         }
     }
@@ -383,85 +974,428 @@ impl Screen {
         if is_special_byte(byte) {
             self.handle_special_byte(byte, rasterizer);
         } else {
+            let cell_state = match self.decode_byte(byte) {
+                Some(cell_state) => cell_state,
+                // The character isn't complete yet.
+                None => return
+            };
+            
+            if let CellState::Filled(grapheme) = &cell_state {
+                if self.attach_to_previous_cell(grapheme.base(), rasterizer) {
+                    return;
+                }
+            }
+            
+            // The line is only wrapped once there's something to print on the next one, so that a
+            // line filled up to the last column and followed by a line feed isn't.
+            if self.cursor.wrap_pending && self.state.auto_wrap {
+                let (row_number, _) = self.get_position_pointed_by_cursor();
+                self.screen_lines[row_number].wrapped = true;
+                
+                self.cursor.position.x = 1;
+                self.next_line(rasterizer);
+            }
+            
+            self.cursor.wrap_pending = false;
+            
             let (mut row_number, mut column_number) = self.get_position_pointed_by_cursor();
             
-            let cell_state = self.screen_lines[row_number].cells[column_number].state.next_state(byte);
+            let wide = cell_state.is_wide() && self.line_cell_width > 1;
             
-            let advance = match cell_state {
-                CellState::Filled(_) | CellState::Invalid => true,
-                _ => false
-            };
+            // A wide character doesn't fit in the last column. Without DECAWM, it goes in the
+            // last two columns instead.
+            if wide && column_number + 1 >= self.line_cell_width && !self.state.auto_wrap {
+                column_number = self.line_cell_width - 2;
+            }
             
-            self.screen_lines[row_number].cells[column_number].state = cell_state;
-            self.screen_lines[row_number].cells[column_number].properties = self.cursor.properties;
+            // Otherwise, the last column is left empty, and the character goes at the start of
+            // the next line.
+            if wide && column_number + 1 >= self.line_cell_width {
+                let line = &mut self.screen_lines[row_number];
+                
+                line.cells[column_number].state = CellState::Empty;
+                line.fix_wide_characters(column_number, column_number + 1);
+                line.wrapped = true;
+                line.wide_padding = true;
+                line.rasterize(rasterizer);
+                
+                self.cursor.position.x = 1;
+                self.cursor.position.y = row_number + 1;
+                self.next_line(rasterizer);
+                
+                let position = self.get_position_pointed_by_cursor();
+                row_number = position.0;
+                column_number = position.1;
+            }
             
-            self.screen_lines[row_number].rasterize(rasterizer);
+            let columns = if wide { 2 } else { 1 };
             
-            if advance {
-                column_number += 1;
-                if column_number >= self.line_cell_width {
-                    row_number += 1;
-                    column_number = 0;
-                    if row_number >= self.line_cell_height {
-                        self.push_line_to_history(rasterizer);
-                    }
-                }
+            // With IRM, the cells under and after the cursor are shifted to make room.
+            if self.state.insert_mode {
+                self.cursor.position.x = column_number + 1;
+                self.cursor.position.y = row_number + 1;
+                self.insert_cells(columns, rasterizer);
+            }
+            
+            let properties = self.cursor.properties;
+            let line = &mut self.screen_lines[row_number];
+            
+            line.cells[column_number].state = cell_state;
+            line.cells[column_number].properties = properties;
+            
+            if wide {
+                line.cells[column_number + 1] = Cell {
+                    state: CellState::Spacer,
+                    properties
+                };
             }
             
-            self.cursor.position.x = column_number + 1;
+            line.fix_wide_characters(column_number, column_number + columns);
+            line.rasterize(rasterizer);
+            
+            // The last cell holds content again.
+            if column_number + columns >= self.line_cell_width {
+                line.wide_padding = false;
+            }
+            
+            column_number += columns;
+            
             self.cursor.position.y = row_number + 1;
+            
+            // The cursor stays on the last column, until the next character goes to the next
+            // line. Without DECAWM, the next character replaces the last one instead.
+            if column_number >= self.line_cell_width {
+                self.cursor.position.x = self.line_cell_width;
+                self.cursor.wrap_pending = true;
+            } else {
+                self.cursor.position.x = column_number + 1;
+            }
+        }
+        
+    }
+    
+    // Feed a byte to the UTF-8 decoder, giving the content of a cell once a character is complete.
+    // Characters go through the character set they were printed with.
+    fn decode_byte(&mut self, byte: u8) -> Option<CellState> {
+        let character = match self.decoder.decode_byte(byte) {
+            DecodeState::Continue => return None,
+            DecodeState::Done(character) => character,
+            DecodeState::Rewind => {
+                match self.decoder.decode_byte(byte) {
+                    DecodeState::Continue        => return None,
+                    DecodeState::Done(character) => character,
+                    _                            => return Some(CellState::Invalid)
+                }
+            },
+            DecodeState::Error => return Some(CellState::Invalid)
+        };
+        
+        let character = self.state.charsets.translate(character);
+        
+        Some(CellState::Filled(Grapheme::new(character)))
+    }
+    
+    // Zero width characters don't get a cell of their own, but are attached to the character
+    // before them. Returns whether the character was attached.
+    fn attach_to_previous_cell(&mut self, character: char, rasterizer: &mut Rasterizer) -> bool {
+        let (row_number, column_number) = match self.get_previous_cell_position() {
+            Some(position) => position,
+            None => return false
+        };
+        
+        let line = &mut self.screen_lines[row_number];
+        
+        if let CellState::Filled(grapheme) = &mut line.cells[column_number].state {
+            if grapheme.accepts(character) {
+                grapheme.attach(character);
+                line.rasterize(rasterizer);
+                
+                return true;
+            }
         }
         
+        false
     }
     
-    fn push_line_to_history(&mut self, rasterizer: &mut Rasterizer) {
-        let line = self.screen_lines.remove(0);
+    // Position of the cell written before the cursor, either on the cursor line or at the end of
+    // the line above when it wrapped, or the cell under the cursor when it stayed on the last
+    // column after printing there, with or without DECAWM.
+    // Spacers give the position of their wide character.
+    fn get_previous_cell_position(&self) -> Option<(usize, usize)> {
+        let (row_number, column_number) = self.get_position_pointed_by_cursor();
+        
+        let (row_number, column_number) = if self.cursor.wrap_pending {
+            (row_number, column_number)
+        } else if column_number > 0 {
+            (row_number, column_number - 1)
+        } else if row_number > 0 && self.screen_lines[row_number - 1].wrapped {
+            (row_number - 1, self.line_cell_width - 1)
+        } else {
+            return None;
+        };
+        
+        if column_number > 0 && self.screen_lines[row_number].cells[column_number].state.is_spacer() {
+            Some((row_number, column_number - 1))
+        } else {
+            Some((row_number, column_number))
+        }
+    }
+    
+    // Scroll the lines of the scroll region up, adding blank lines at its bottom.
+    // Lines going past the top of the screen are pushed to the history, as long as the scroll
+    // region is the whole screen.
+    pub fn scroll_up(&mut self, count: usize, rasterizer: &mut Rasterizer) {
+        let ScrollRegion { top, bottom } = self.scroll_region;
+        let blank = self.erased_line(rasterizer);
         
         // If we're in the alternative buffer state, we don't want to polute the main history.
-        if !self.state.is_alternative {
-            self.history.push_front(line);
+        let history = if !self.state.is_alternative && self.scroll_region.is_full(self.line_cell_height) {
+            Some(&mut self.history)
+        } else {
+            None
+        };
+        
+        let pushed = scroll_lines_up(&mut self.screen_lines, top, bottom, count, &blank, history);
+        self.pushed_to_history = self.pushed_to_history.wrapping_add(pushed);
+    }
+    
+    // Scroll the lines of the scroll region down, adding blank lines at its top.
+    pub fn scroll_down(&mut self, count: usize, rasterizer: &mut Rasterizer) {
+        let ScrollRegion { top, bottom } = self.scroll_region;
+        let blank = self.erased_line(rasterizer);
+        
+        scroll_lines_down(&mut self.screen_lines, top, bottom, count, &blank);
+    }
+    
+    // Add blank lines at the cursor, pushing the lines below it down to the bottom of the scroll
+    // region. Does nothing if the cursor is outside of the scroll region.
+    pub fn insert_lines(&mut self, count: usize, rasterizer: &mut Rasterizer) {
+        let ScrollRegion { top, bottom } = self.scroll_region;
+        let cursor_y = self.cursor.position.y;
+        
+        if (top..=bottom).contains(&cursor_y) {
+            let blank = self.erased_line(rasterizer);
+            
+            scroll_lines_down(&mut self.screen_lines, cursor_y, bottom, count, &blank);
         }
+    }
+    
+    // Remove lines from the cursor, pulling the lines below it up and adding blank lines at the
+    // bottom of the scroll region. Does nothing if the cursor is outside of the scroll region.
+    pub fn delete_lines(&mut self, count: usize, rasterizer: &mut Rasterizer) {
+        let ScrollRegion { top, bottom } = self.scroll_region;
+        let cursor_y = self.cursor.position.y;
         
-        let mut new = CellLine::new(self.line_cell_width, CellProperties::new());
-        new.rasterize(rasterizer);
-        self.screen_lines.push(new);
+        if (top..=bottom).contains(&cursor_y) {
+            let blank = self.erased_line(rasterizer);
+            
+            scroll_lines_up(&mut self.screen_lines, cursor_y, bottom, count, &blank, None);
+        }
+    }
+    
+    // Properties of erased cells: only the background color of the cursor is kept.
+    pub fn erase_properties(&self) -> CellProperties {
+        CellProperties {
+            bg: self.cursor.properties.bg,
+            ..CellProperties::new()
+        }
     }
+    
+    fn erased_line(&self, rasterizer: &mut Rasterizer) -> CellLine {
+        let mut line = CellLine::new(self.line_cell_width, self.erase_properties());
+        line.rasterize(rasterizer);
+        line
+    }
+    
+    // Erase the cells of the cursor line between start and end (0 based, exclusive).
+    pub fn erase_cells(&mut self, start: usize, end: usize, rasterizer: &mut Rasterizer) {
+        let properties = self.erase_properties();
+        let line = &mut self.screen_lines[self.cursor.position.y - 1];
+        
+        line.erase_cells(start, end, properties);
+        line.rasterize(rasterizer);
+    }
+    
+    // Add blank cells at the cursor, pushing the cells after it to the right. Cells going past
+    // the last column are lost.
+    pub fn insert_cells(&mut self, count: usize, rasterizer: &mut Rasterizer) {
+        let properties = self.erase_properties();
+        let line = &mut self.screen_lines[self.cursor.position.y - 1];
+        
+        line.insert_cells(self.cursor.position.x - 1, count, properties);
+        line.rasterize(rasterizer);
+    }
+    
+    // Remove cells from the cursor, pulling the cells after it to the left and adding blank cells
+    // at the end of the line.
+    pub fn delete_cells(&mut self, count: usize, rasterizer: &mut Rasterizer) {
+        let properties = self.erase_properties();
+        let line = &mut self.screen_lines[self.cursor.position.y - 1];
+        
+        line.delete_cells(self.cursor.position.x - 1, count, properties);
+        line.rasterize(rasterizer);
+    }
+}
+
+// Scroll the lines between top and bottom (1 based, inclusive) up, adding copies of `blank` at
+// the bottom. Lines going past the top are pushed to `history` if there is one.
+// Returns the number of lines pushed to the history.
+fn scroll_lines_up(
+    lines: &mut [CellLine],
+    top: usize,
+    bottom: usize,
+    count: usize,
+    blank: &CellLine,
+    mut history: Option<&mut History>
+) -> usize {
+    let count = count.min(bottom + 1 - top);
+    let region = &mut lines[top - 1..bottom];
+    let kept = region.len() - count;
+    
+    // The lines going out end up at the bottom, in the same order.
+    region.rotate_left(count);
+    
+    for line in &mut region[kept..] {
+        let line = std::mem::replace(line, blank.clone());
+        
+        if let Some(history) = history.as_mut() {
+            history.push(&line);
+        }
+    }
+    
+    if history.is_some() {
+        count
+    } else {
+        0
+    }
+}
+
+// Scroll the lines between top and bottom (1 based, inclusive) down, adding copies of `blank` at
+// the top.
+fn scroll_lines_down(lines: &mut [CellLine], top: usize, bottom: usize, count: usize, blank: &CellLine) {
+    let count = count.min(bottom + 1 - top);
+    let region = &mut lines[top - 1..bottom];
+    
+    region.rotate_right(count);
+    
+    for line in &mut region[..count] {
+        *line = blank.clone();
+    }
+}
+
+// Resize a screen to `width` columns and `height` rows, keeping its cursor on the same line.
+// When shrinking, empty space below the cursor is removed first, then lines from the top, which
+// are pushed to `history` if there is one.
+// When growing, lines are taken back from `history` first, then empty lines are added at the
+// bottom.
+// Returns the number of lines moved to the history, negative when lines were taken back from it.
+// Lines aren't rasterized.
+fn resize_lines(
+    lines: &mut Vec<CellLine>,
+    cursor: &mut Cursor,
+    mut history: Option<&mut History>,
+    width: usize,
+    height: usize
+) -> isize {
+    let mut moved = 0isize;
+    
+    for line in lines.iter_mut() {
+        line.resize(width);
+    }
+
+    if lines.len() > height {
+        // Remove lines below the cursor
+        let cursor_y = cursor.position.y.min(lines.len());
+        let below_cursor = lines.len() - cursor_y;
+        let to_remove = (lines.len() - height).min(below_cursor);
+
+        lines.truncate(lines.len() - to_remove);
+
+        // Then from the top
+        let to_remove = lines.len().saturating_sub(height);
+
+        for line in lines.drain(0..to_remove) {
+            if let Some(history) = history.as_mut() {
+                history.push(&line);
+                moved += 1;
+            }
+        }
+
+        cursor.position.y = cursor.position.y.saturating_sub(to_remove);
+    } else {
+        while lines.len() < height {
+            let from_history = match history.as_mut() {
+                Some(history) => history.pop(),
+                None => None
+            };
+
+            match from_history {
+                Some(line) => {
+                    lines.insert(0, line.to_cell_line(width));
+                    cursor.position.y += 1;
+                    moved -= 1;
+                },
+                None => lines.push(CellLine::new(width, CellProperties::new()))
+            }
+        }
+    }
+
+    cursor.clamp(width, height);
+    
+    moved
 }
 
 pub struct PtyBuffer {
+    config: Config,
     rasterizer: WrappedRasterizer,
     screen: Screen,
     updated: bool,
+    /// Number of lines the view is scrolled up into the history, 0 being the bottom of the screen.
+    scroll_offset: usize,
+    selection: Option<Selection>,
+    /// History lines in view, by grid line. They don't keep any rendering data, so they're
+    /// rebuilt and rasterized when they come into view, and dropped when they leave it.
+    history_cache: HashMap<isize, CellLine>,
 }
 
 impl PtyBuffer {
-    pub fn new(rasterizer: WrappedRasterizer, sender: mio_extras::channel::Sender<ScreenEvent>, id: usize) -> PtyBuffer {    
-        let screen = Screen::empty(sender, &mut rasterizer.write().unwrap(), id);
+    pub fn new(config: Config, rasterizer: WrappedRasterizer, sender: mio_extras::channel::Sender<ScreenEvent>, id: usize) -> PtyBuffer {    
+        let screen = Screen::empty(sender, &mut rasterizer.write().unwrap(), id, config.scrollback.lines);
         
         Self {
+            config,
             rasterizer,
             screen,
-            updated: false
+            updated: false,
+            scroll_offset: 0,
+            selection: None,
+            history_cache: HashMap::new(),
         }
     }
     
     pub fn add_input(&mut self, input: Vec<u8>) {
         self.updated = true;
         
-        let mut lines = input.split(|x| x == &LINE_FEED_BYTE).peekable();
+        let pushed_to_history = self.screen.pushed_to_history;
         
-        loop {
-            let next = lines.next();
-            let is_last = lines.peek().is_none();
-            match next {
-                Some(data) => {
-                    self.add_to_screen_buffer(data);                 
-                    if !is_last {
-                        self.complete_line();
-                    }
-                },
-                None => break
-            };
+        self.add_to_screen_buffer(&input);
+        
+        // Lines dropped from the history can't be shown anymore.
+        let oldest = self.screen.pushed_to_history as isize - self.screen.history.len() as isize;
+        self.history_cache.retain(|&line, _| line >= oldest);
+        
+        if self.screen.state.is_alternative {
+            // The alternate screen has no history to scroll into.
+            self.set_scroll_offset(0);
+        } else if self.config.scrollback.scroll_on_output {
+            self.set_scroll_offset(0);
+        } else {
+            // Follow the lines that were just pushed to the history, so the view stays in place.
+            let pushed = self.screen.pushed_to_history.wrapping_sub(pushed_to_history);
+            
+            if self.scroll_offset != 0 {
+                self.set_scroll_offset(self.scroll_offset.saturating_add(pushed));
+            }
         }
     }
     
@@ -470,35 +1404,180 @@ impl PtyBuffer {
     }
     
     // Get a range of lines (from the last one pushed, aka the newest, to the first one pushed, aka the oldest)
+    // 0 is the bottom of the view, which is above the bottom of the screen when scrolled up into
+    // the history.
     // Won't panic if there's more
     // Will panic if end < start
     pub fn get_range(&mut self, start: usize, end: usize) -> Vec<DisplayCellLine> {
         assert!(start <= end);
         self.updated = false;
         
-        let mut display_lines: Vec<Vec<DisplayCellLine>> = vec![];
-        for line in self.screen.screen_lines.iter() {
-            display_lines.push(line.display.clone());
+        let screen_height = self.screen.screen_lines.len();
+        let cursor_index = screen_height - self.screen.cursor.position.y.min(screen_height);
+        
+        let mut display_lines: Vec<DisplayCellLine> = Vec::with_capacity(end - start);
+        let selection = self.selection.and_then(|selection| self.screen.get_selection_range(&selection));
+        
+        for index in (start + self.scroll_offset)..(end + self.scroll_offset) {
+            let grid_line = self.screen.pushed_to_history as isize + (screen_height - 1) as isize - index as isize;
+            
+            let line = if grid_line < self.screen.pushed_to_history as isize {
+                if !self.history_cache.contains_key(&grid_line) {
+                    let mut line = match self.screen.get_grid_line(grid_line) {
+                        Some(line) => line.into_owned(),
+                        None => break
+                    };
+                    
+                    line.rasterize(&mut self.rasterizer.write().unwrap());
+                    self.history_cache.insert(grid_line, line);
+                }
+                
+                &self.history_cache[&grid_line]
+            } else {
+                match self.screen.get_grid_line(grid_line) {
+                    Some(Cow::Borrowed(line)) => line,
+                    _ => break
+                }
+            };
+            
+            let mut lines = line.display.clone();
+            
+            if index == cursor_index {
+                let display_index = line.get_display_index(self.screen.cursor.position.x - 1);
+                
+                if let Some(line) = lines.first_mut() {
+                    if let Some(cell) = line.cells.get_mut(display_index) {
+                        cell.is_cursor = true;
+                    }
+                }
+            }
+            
+            if let Some((selection_start, selection_end)) = selection {
+                if selection_start.line <= grid_line && grid_line <= selection_end.line {
+                    let first = if grid_line == selection_start.line { selection_start.column } else { 0 };
+                    let last = if grid_line == selection_end.line { selection_end.column } else { line.cells.len() };
+                    
+                    if let Some(display_line) = lines.first_mut() {
+                        for column in first..(last + 1).min(line.cells.len()) {
+                            if let Some(cell) = display_line.cells.get_mut(line.get_display_index(column)) {
+                                cell.selected = true;
+                            }
+                        }
+                    }
+                }
+            }
+            
+            display_lines.extend(lines.into_iter().rev());
+        }
+        
+        display_lines
+    }
+    
+    pub fn scroll(&mut self, scroll: ScrollView) {
+        // The alternative screen doesn't have any history.
+        if self.screen.state.is_alternative {
+            return;
         }
         
-        let mut display_lines: Vec<DisplayCellLine> = display_lines.iter().flatten().cloned().collect();
+        let page = self.screen.line_cell_height.saturating_sub(1).max(1);
+        
+        let offset = match scroll {
+            ScrollView::Up(lines) => self.scroll_offset.saturating_add(lines),
+            ScrollView::Down(lines) => self.scroll_offset.saturating_sub(lines),
+            ScrollView::PageUp => self.scroll_offset.saturating_add(page),
+            ScrollView::PageDown => self.scroll_offset.saturating_sub(page),
+        };
+        
+        self.set_scroll_offset(offset);
+    }
+    
+    // Cell of the grid shown at a column and a row of the view (1 based).
+    fn get_grid_point(&self, column: usize, row: usize) -> GridPoint {
+        let row = row.max(1).min(self.screen.line_cell_height) - 1;
+        
+        GridPoint {
+            line: self.screen.pushed_to_history as isize + row as isize - self.scroll_offset as isize,
+            column: column.max(1).min(self.screen.line_cell_width) - 1
+        }
+    }
+    
+    pub fn start_selection(&mut self, column: usize, row: usize, kind: SelectionKind) {
+        let point = self.get_grid_point(column, row);
         
-        if let Some(line) = display_lines.get_mut(self.screen.cursor.position.y - 1) {
-            if let Some(cell) = line.cells.get_mut(self.screen.cursor.position.x - 1) {
-                cell.is_cursor = true;
+        self.selection = Some(Selection::new(point, kind));
+        self.updated = true;
+    }
+    
+    pub fn extend_selection(&mut self, column: usize, row: usize) {
+        let point = self.get_grid_point(column, row);
+        
+        if let Some(selection) = &mut self.selection {
+            if selection.end != point {
+                selection.end = point;
+                self.updated = true;
             }
         }
+    }
+    
+    pub fn clear_selection(&mut self) {
+        if self.selection.take().is_some() {
+            self.updated = true;
+        }
+    }
+    
+    pub fn get_selection_text(&self) -> Option<String> {
+        self.selection.and_then(|selection| self.screen.get_selection_text(&selection))
+    }
+    
+    // Id and URI of the link of the cell at a column and a row of the view (1 based).
+    pub fn get_hyperlink(&self, column: usize, row: usize) -> Option<(u32, String)> {
+        let point = self.get_grid_point(column, row);
+        let line = self.screen.get_grid_line(point.line)?;
+        let id = line.cells.get(point.column)?.properties.hyperlink?;
         
-        display_lines.reverse();
+        self.screen.get_hyperlink(id).map(|link| (id, link.uri.clone()))
+    }
+    
+    fn set_scroll_offset(&mut self, offset: usize) {
+        let offset = offset.min(self.screen.history.len());
         
-        display_lines
+        if offset != self.scroll_offset {
+            self.scroll_offset = offset;
+            self.updated = true;
+            
+            // Lines scrolled out of view are dropped from the cache.
+            let top = self.screen.pushed_to_history as isize - offset as isize;
+            let bottom = top + self.screen.line_cell_height as isize;
+            
+            self.history_cache.retain(|&line, _| top <= line && line < bottom);
+        }
     }
     
-    pub fn dimensions_updated(&mut self) {        
-        // for line in self.screen.history.iter_mut() {
-        //     line.rasterize_to_cells(&mut self.rasterizer.write().unwrap());
-        // }
+    // Should be called after the rasterizer dimensions have been updated.
+    pub fn dimensions_updated(&mut self) {
+        let mut rasterizer = self.rasterizer.write().unwrap();
+        let line_cell_size = rasterizer.get_line_cell_size();
+
+        self.screen.update_line_cell_dimensions(line_cell_size, &mut rasterizer);
         
+        // Rewrapped lines don't hold the same cells anymore.
+        self.selection = None;
+        
+        // The history may have been rewrapped.
+        self.scroll_offset = self.scroll_offset.min(self.screen.history.len());
+        self.history_cache.clear();
+
+        self.updated = true;
+    }
+    
+    // The glyphs of the screen are rendered at the new font size.
+    pub fn font_updated(&mut self) {
+        self.screen.rasterize_lines(&mut self.rasterizer.write().unwrap());
+        self.history_cache.clear();
+        self.updated = true;
+    }
+    
+    pub fn mark_updated(&mut self) {
         self.updated = true;
     }
     
@@ -506,7 +1585,180 @@ impl PtyBuffer {
         self.screen.add_to_buffer(data, &mut self.rasterizer.write().unwrap());
     }
     
-    fn complete_line(&mut self) {
-        self.screen.next_line(&mut self.rasterizer.write().unwrap());
+    pub fn get_state(&self) -> ScreenState {
+        self.screen.state
+    }
+    
+    // Style of the cursor, or None if it's hidden.
+    pub fn get_cursor_style(&self) -> Option<CursorStyle> {
+        let state = self.screen.state;
+        
+        if state.cursor_visible {
+            Some(state.cursor_style)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Wide characters take two cells.
+    fn line(text: &str) -> CellLine {
+        let mut cells: Vec<Cell> = vec![];
+        
+        for character in text.chars() {
+            let state = if character == ' ' { CellState::Empty } else { CellState::Filled(Grapheme::new(character)) };
+            let wide = state.is_wide();
+            
+            cells.push(Cell {
+                state,
+                properties: CellProperties::new()
+            });
+            
+            if wide {
+                cells.push(Cell {
+                    state: CellState::Spacer,
+                    properties: CellProperties::new()
+                });
+            }
+        }
+        
+        CellLine::from_cells(cells, false)
+    }
+    
+    fn lines(texts: &[&str]) -> Vec<CellLine> {
+        texts.iter().map(|text| line(text)).collect()
+    }
+    
+    // Characters of a line, with spaces for empty cells and without spacers.
+    fn text(line: &CellLine) -> String {
+        line.cells.iter()
+            .filter_map(|cell| match &cell.state {
+                CellState::Filled(grapheme) => Some(grapheme.base()),
+                CellState::Empty => Some(' '),
+                CellState::Invalid => Some('�'),
+                CellState::Spacer => None
+            })
+            .collect()
+    }
+    
+    fn texts(lines: &[CellLine]) -> Vec<String> {
+        lines.iter().map(text).collect()
+    }
+    
+    #[test]
+    fn zero_width_characters_join_the_grapheme_before_them() {
+        let mut grapheme = Grapheme::new('e');
+        
+        assert!(grapheme.accepts('\u{301}'));
+        assert!(!grapheme.accepts('f'));
+        assert!(!grapheme.accepts('\u{7}'));
+        
+        grapheme.attach('\u{301}');
+        
+        let mut text = String::new();
+        grapheme.push_to(&mut text);
+        assert_eq!(text, "e\u{301}");
+        
+        // Variation selectors, and anything after a zero width joiner
+        assert!(Grapheme::new('❤').accepts('\u{FE0F}'));
+        
+        let mut emoji = Grapheme::new('👩');
+        emoji.attach(ZERO_WIDTH_JOINER);
+        assert!(emoji.accepts('💻'));
+    }
+    
+    #[test]
+    fn inserted_cells_push_the_end_of_the_line_out() {
+        let mut line = line("abcdef");
+        
+        line.insert_cells(2, 2, CellProperties::new());
+        assert_eq!(text(&line), "ab  cd");
+        
+        line.insert_cells(4, 10, CellProperties::new());
+        assert_eq!(text(&line), "ab    ");
+    }
+    
+    #[test]
+    fn deleted_cells_are_replaced_at_the_end_of_the_line() {
+        let mut line = line("abcdef");
+        
+        line.delete_cells(1, 2, CellProperties::new());
+        assert_eq!(text(&line), "adef  ");
+        
+        line.delete_cells(2, 10, CellProperties::new());
+        assert_eq!(text(&line), "ad    ");
+    }
+    
+    #[test]
+    fn erased_cells_keep_the_given_properties() {
+        let blue_background = CellProperties {
+            bg: Some(Color::from_rgb(0, 0, 255)),
+            ..CellProperties::new()
+        };
+        
+        let mut line = line("abcdef");
+        line.erase_cells(1, 3, blue_background);
+        
+        assert_eq!(text(&line), "a  def");
+        assert_eq!(line.cells[1].properties, blue_background);
+        assert_eq!(line.cells[3].properties, CellProperties::new());
+        
+        line.erase_cells(4, 10, CellProperties::new());
+        assert_eq!(text(&line), "a  d  ");
+    }
+    
+    #[test]
+    fn wide_characters_go_away_with_either_half() {
+        let mut erased = line("a漢b");
+        erased.erase_cells(2, 3, CellProperties::new());
+        assert_eq!(text(&erased), "a  b");
+        
+        let mut deleted = line("a漢b");
+        deleted.delete_cells(1, 1, CellProperties::new());
+        assert_eq!(text(&deleted), "a b ");
+        
+        // The spacer is pushed past the end of the line.
+        let mut inserted = line("ab漢");
+        inserted.insert_cells(0, 1, CellProperties::new());
+        assert_eq!(text(&inserted), " ab ");
+    }
+    
+    #[test]
+    fn scrolling_up_only_moves_the_lines_of_the_region() {
+        let mut lines = lines(&["a", "b", "c", "d", "e"]);
+        
+        let pushed = scroll_lines_up(&mut lines, 2, 4, 1, &line(" "), None);
+        
+        assert_eq!(texts(&lines), vec!["a", "c", "d", " ", "e"]);
+        assert_eq!(pushed, 0);
+    }
+    
+    #[test]
+    fn lines_scrolled_up_go_to_the_history() {
+        let mut lines = lines(&["a", "b", "c"]);
+        let mut history = History::new(100);
+        
+        let pushed = scroll_lines_up(&mut lines, 1, 3, 2, &line(" "), Some(&mut history));
+        
+        assert_eq!(texts(&lines), vec!["c", " ", " "]);
+        assert_eq!(pushed, 2);
+        assert_eq!(text(&history.get(0).unwrap().to_cell_line(1)), "b");
+        assert_eq!(text(&history.get(1).unwrap().to_cell_line(1)), "a");
+    }
+    
+    #[test]
+    fn scrolling_down_only_moves_the_lines_of_the_region() {
+        let mut lines = lines(&["a", "b", "c", "d", "e"]);
+        
+        scroll_lines_down(&mut lines, 2, 4, 1, &line(" "));
+        assert_eq!(texts(&lines), vec!["a", " ", "b", "c", "e"]);
+        
+        // Scrolling by more than the size of the region clears it.
+        scroll_lines_down(&mut lines, 2, 4, 10, &line(" "));
+        assert_eq!(texts(&lines), vec!["a", " ", " ", " ", "e"]);
     }
 }
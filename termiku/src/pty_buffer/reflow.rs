@@ -0,0 +1,351 @@
+use super::*;
+
+// Rewrap a screen and its history to `width` columns, then fit the result in `height` rows.
+//
+// Consecutive lines joined by a soft wrap (see `CellLine::wrapped`) form a single logical line,
+// which is split again according to the new width. Trailing blank cells of each logical line are
+// dropped, so that narrowing then widening the screen gives back the original lines. Wide
+// characters are never split between two lines.
+//
+// The cursor and its saved position stay on the same logical character, as long as the lines
+// after the cursor fit on the screen. Lines that don't fit on the screen anymore go to the
+// history, and lines are taken back from the history when there's room for them.
+// Returns the number of lines moved to the history, negative when more were taken back from it.
+// Lines aren't rasterized.
+pub(super) fn reflow_lines(
+    lines: &mut Vec<CellLine>,
+    cursor: &mut Cursor,
+    history: &mut History,
+    width: usize,
+    height: usize
+) -> isize {
+    // Row and column of the cursor, then of its saved position if there's one.
+    let anchors: Vec<(usize, usize)> = std::iter::once(cursor.position)
+        .chain(cursor.saved)
+        .map(|position| (position.y.min(lines.len()).max(1) - 1, position.x - 1))
+        .collect();
+    
+    // Blank lines below the cursor and its saved position aren't content: they would otherwise
+    // push lines into the history when the content gets taller.
+    let last_row = anchors.iter().map(|&(row, _)| row).max().unwrap_or(0);
+    
+    while lines.len() > last_row + 1 && lines.last().map_or(false, CellLine::is_blank) {
+        lines.pop();
+    }
+    
+    let history_length = history.len();
+    
+    let physical_lines = history.drain()
+        .map(|line| (line.to_cells(), line.wrapped, line.wide_padding))
+        .chain(lines.drain(..).map(|line| (line.cells, line.wrapped, line.wide_padding)));
+    
+    let mut logical_lines: Vec<Vec<Cell>> = vec![];
+    let mut current: Vec<Cell> = vec![];
+    // Logical line index of each anchor, and its offset inside this line.
+    let mut logical_anchors = vec![(0usize, 0usize); anchors.len()];
+    
+    for (index, (mut cells, wrapped, wide_padding)) in physical_lines.enumerate() {
+        for (anchor, &(row, column)) in anchors.iter().enumerate() {
+            if index == history_length + row {
+                logical_anchors[anchor] = (logical_lines.len(), current.len() + column);
+            }
+        }
+        
+        // The last cell of a wrapped line isn't part of the content when a wide character didn't
+        // fit in it. Any other empty cell, like a space, is.
+        if wrapped && wide_padding {
+            if let Some(CellState::Empty) = cells.last().map(|cell| &cell.state) {
+                cells.pop();
+            }
+        }
+        
+        current.extend(cells);
+        
+        if !wrapped {
+            logical_lines.push(std::mem::replace(&mut current, vec![]));
+        }
+    }
+    
+    if !current.is_empty() {
+        logical_lines.push(current);
+    }
+    
+    let mut new_lines: Vec<CellLine> = vec![];
+    // New line index and column of each anchor
+    let mut new_anchors = vec![(0usize, 0usize); anchors.len()];
+    
+    for (index, mut cells) in logical_lines.into_iter().enumerate() {
+        // The cells under the anchors must be kept, even if they're blank.
+        let minimum_length = logical_anchors.iter()
+            .filter(|&&(line, _)| line == index)
+            .map(|&(_, offset)| offset + 1)
+            .max()
+            .unwrap_or(0);
+        
+        while cells.len() > minimum_length && cells.last().map_or(false, Cell::is_blank) {
+            cells.pop();
+        }
+        
+        cells.resize(cells.len().max(minimum_length), Cell::empty(CellProperties::new()));
+        
+        let mut line: Vec<Cell> = Vec::with_capacity(width);
+        
+        for (offset, cell) in cells.into_iter().enumerate() {
+            // A wide character can't be split, so it goes on the next line along with its spacer
+            // when only one column is left.
+            let full = line.len() == width || (line.len() + 1 == width && cell.state.is_wide());
+            
+            if full && !line.is_empty() {
+                let wide_padding = line.len() < width;
+                line.resize(width, Cell::empty(CellProperties::new()));
+                
+                let cells = std::mem::replace(&mut line, Vec::with_capacity(width));
+                let mut wrapped_line = CellLine::from_cells(cells, true);
+                wrapped_line.wide_padding = wide_padding;
+                new_lines.push(wrapped_line);
+            }
+            
+            for (anchor, &logical) in logical_anchors.iter().enumerate() {
+                if logical == (index, offset) {
+                    new_anchors[anchor] = (new_lines.len(), line.len());
+                }
+            }
+            
+            line.push(cell);
+        }
+        
+        let mut last = CellLine::from_cells(line, false);
+        last.resize(width);
+        new_lines.push(last);
+    }
+    
+    let new_cursor_index = new_anchors[0].0;
+    
+    // Blank lines after the cursor can go when the lines from the cursor on don't fit on the
+    // screen anymore.
+    while new_lines.len() > new_cursor_index + height && new_lines.last().map_or(false, CellLine::is_blank) {
+        new_lines.pop();
+    }
+    
+    // Fill the screen with the newest lines, the ones above going to the history. When the lines
+    // after the cursor still don't fit, the line of the cursor goes to the history too rather
+    // than losing any content, and the cursor is left on the first row.
+    let split = new_lines.len().saturating_sub(height);
+    
+    let mut screen_lines = new_lines.split_off(split);
+    
+    while screen_lines.len() < height {
+        screen_lines.push(CellLine::new(width, CellProperties::new()));
+    }
+    
+    for line in new_lines.iter() {
+        history.push(line);
+    }
+    
+    *lines = screen_lines;
+    
+    cursor.position.x = new_anchors[0].1 + 1;
+    cursor.position.y = new_cursor_index.saturating_sub(split) + 1;
+    
+    if let Some(saved) = &mut cursor.saved {
+        saved.x = new_anchors[1].1 + 1;
+        saved.y = new_anchors[1].0.saturating_sub(split) + 1;
+    }
+    
+    cursor.clamp(width, height);
+    
+    new_lines.len() as isize - history_length as isize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // A line of `width` cells holding `text`, wide characters taking two of them.
+    fn line(text: &str, width: usize, wrapped: bool) -> CellLine {
+        let mut cells: Vec<Cell> = vec![];
+        
+        for character in text.chars() {
+            let state = CellState::Filled(Grapheme::new(character));
+            let wide = state.is_wide();
+            
+            cells.push(Cell {
+                state,
+                properties: CellProperties::new()
+            });
+            
+            if wide {
+                cells.push(Cell {
+                    state: CellState::Spacer,
+                    properties: CellProperties::new()
+                });
+            }
+        }
+        
+        let mut line = CellLine::from_cells(cells, wrapped);
+        line.resize(width);
+        line
+    }
+    
+    // Characters of a line, with spaces for empty cells and without spacers.
+    fn text(line: &CellLine) -> String {
+        line.cells.iter()
+            .filter_map(|cell| match &cell.state {
+                CellState::Filled(grapheme) => Some(grapheme.base()),
+                CellState::Empty => Some(' '),
+                CellState::Invalid => Some('�'),
+                CellState::Spacer => None
+            })
+            .collect()
+    }
+    
+    fn texts(lines: &[CellLine]) -> Vec<String> {
+        lines.iter().map(text).collect()
+    }
+    
+    fn cursor_at(x: usize, y: usize) -> Cursor {
+        let mut cursor = Cursor::new();
+        cursor.position = Position { x, y };
+        cursor
+    }
+    
+    #[test]
+    fn narrowing_splits_a_wrapped_line() {
+        let mut lines = vec![line("abcdef", 6, true), line("gh", 6, false), line("", 6, false)];
+        let mut cursor = cursor_at(3, 2);
+        let mut history = History::new(100);
+        
+        reflow_lines(&mut lines, &mut cursor, &mut history, 4, 3);
+        
+        assert_eq!(texts(&lines), vec!["abcd", "efgh", "    "]);
+        assert_eq!(lines.iter().map(|line| line.wrapped).collect::<Vec<_>>(), vec![true, true, false]);
+        assert_eq!((cursor.position.x, cursor.position.y), (1, 3));
+        assert!(history.is_empty());
+    }
+    
+    #[test]
+    fn narrowing_keeps_the_lines_after_the_cursor() {
+        let mut lines = vec![line("abcd", 4, false), line("ef", 4, false), line("", 4, false)];
+        let mut cursor = cursor_at(1, 1);
+        let mut history = History::new(100);
+        
+        // Blank lines at the bottom make room first.
+        reflow_lines(&mut lines, &mut cursor, &mut history, 2, 3);
+        
+        assert_eq!(texts(&lines), vec!["ab", "cd", "ef"]);
+        assert_eq!((cursor.position.x, cursor.position.y), (1, 1));
+        assert!(history.is_empty());
+        
+        // Then the line of the cursor goes to the history.
+        let moved = reflow_lines(&mut lines, &mut cursor, &mut history, 2, 2);
+        
+        assert_eq!(texts(&lines), vec!["cd", "ef"]);
+        assert_eq!(moved, 1);
+        assert_eq!(text(&history.get(0).unwrap().to_cell_line(2)), "ab");
+        assert_eq!((cursor.position.x, cursor.position.y), (1, 1));
+    }
+    
+    #[test]
+    fn widening_joins_a_wrapped_line() {
+        let mut lines = vec![line("abcd", 4, true), line("efgh", 4, true), line("", 4, false)];
+        let mut cursor = cursor_at(1, 3);
+        let mut history = History::new(100);
+        
+        reflow_lines(&mut lines, &mut cursor, &mut history, 6, 3);
+        
+        assert_eq!(texts(&lines), vec!["abcdef", "gh    ", "      "]);
+        assert_eq!(lines.iter().map(|line| line.wrapped).collect::<Vec<_>>(), vec![true, false, false]);
+        assert_eq!((cursor.position.x, cursor.position.y), (3, 2));
+    }
+    
+    #[test]
+    fn wide_character_at_the_boundary_goes_to_the_next_line() {
+        let mut lines = vec![line("abc漢d", 6, false), line("", 6, false)];
+        let mut cursor = cursor_at(1, 2);
+        let mut history = History::new(100);
+        
+        reflow_lines(&mut lines, &mut cursor, &mut history, 4, 3);
+        
+        assert_eq!(texts(&lines), vec!["abc ", "漢d ", "    "]);
+        assert!(lines[0].wrapped && lines[0].wide_padding);
+        assert!(lines[1].cells[0].state.is_wide() && lines[1].cells[1].state.is_spacer());
+        assert_eq!((cursor.position.x, cursor.position.y), (1, 3));
+        
+        // The empty cell left by the wide character isn't part of the content.
+        reflow_lines(&mut lines, &mut cursor, &mut history, 6, 3);
+        
+        assert_eq!(texts(&lines), vec!["abc漢d", "      ", "      "]);
+        assert!(!lines[0].wrapped);
+        assert_eq!((cursor.position.x, cursor.position.y), (1, 2));
+    }
+    
+    #[test]
+    fn empty_cells_ending_a_wrapped_line_are_content() {
+        // A space printed in the last column
+        let mut lines = vec![line("ab", 3, true), line("d", 3, false)];
+        let mut cursor = cursor_at(2, 2);
+        let mut history = History::new(100);
+        
+        reflow_lines(&mut lines, &mut cursor, &mut history, 6, 2);
+        
+        assert_eq!(texts(&lines), vec!["ab d  ", "      "]);
+        assert_eq!((cursor.position.x, cursor.position.y), (5, 1));
+    }
+    
+    #[test]
+    fn lines_going_past_the_top_go_to_the_history() {
+        let mut lines = vec![line("abcd", 4, false), line("efgh", 4, false)];
+        let mut cursor = cursor_at(1, 2);
+        let mut history = History::new(100);
+        
+        let moved = reflow_lines(&mut lines, &mut cursor, &mut history, 2, 2);
+        
+        assert_eq!(texts(&lines), vec!["ef", "gh"]);
+        assert_eq!(history.len(), 2);
+        assert_eq!(moved, 2);
+        assert_eq!(text(&history.get(0).unwrap().to_cell_line(2)), "cd");
+        assert_eq!(text(&history.get(1).unwrap().to_cell_line(2)), "ab");
+        assert_eq!((cursor.position.x, cursor.position.y), (1, 1));
+        
+        // They are taken back when there's room for them again.
+        let moved = reflow_lines(&mut lines, &mut cursor, &mut history, 4, 2);
+        
+        assert_eq!(texts(&lines), vec!["abcd", "efgh"]);
+        assert!(history.is_empty());
+        assert_eq!(moved, -2);
+        assert_eq!((cursor.position.x, cursor.position.y), (1, 2));
+    }
+    
+    #[test]
+    fn cursor_and_saved_position_stay_on_their_characters() {
+        let mut lines = vec![line("abcd", 4, true), line("ef", 4, false)];
+        // On 'f', and saved on 'c'
+        let mut cursor = cursor_at(2, 2);
+        cursor.saved = Some(Position { x: 3, y: 1 });
+        let mut history = History::new(100);
+        
+        let moved = reflow_lines(&mut lines, &mut cursor, &mut history, 2, 2);
+        
+        assert_eq!(texts(&lines), vec!["cd", "ef"]);
+        assert_eq!(history.len(), 1);
+        assert_eq!(moved, 1);
+        assert_eq!((cursor.position.x, cursor.position.y), (2, 2));
+        
+        let saved = cursor.saved.unwrap();
+        assert_eq!((saved.x, saved.y), (1, 1));
+    }
+    
+    #[test]
+    fn pending_wrap_moves_after_its_character_when_widening() {
+        let mut lines = vec![line("abc", 3, false), line("", 3, false)];
+        let mut cursor = cursor_at(3, 1);
+        cursor.wrap_pending = true;
+        let mut history = History::new(100);
+        
+        reflow_lines(&mut lines, &mut cursor, &mut history, 5, 2);
+        
+        assert_eq!(texts(&lines), vec!["abc  ", "     "]);
+        assert_eq!((cursor.position.x, cursor.position.y), (4, 1));
+        assert!(!cursor.wrap_pending);
+    }
+}
@@ -0,0 +1,195 @@
+use super::*;
+
+// Characters ending a word selected by a double click, along with whitespace
+const WORD_SEPARATORS: &str = "\"'`()[]{}<>|;,";
+
+// What a click selects, and what dragging from it extends the selection by
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SelectionKind {
+    Simple,
+    Word,
+    Line
+}
+
+// A cell of the screen or of the history.
+// Lines are counted from the top of the screen when it was created, so that a point stays on the
+// same content when new output pushes lines to the history. Lines above it are negative.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GridPoint {
+    pub line: isize,
+    // 0 based
+    pub column: usize
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Selection {
+    // Where the selection started, and where it was dragged to
+    pub anchor: GridPoint,
+    pub end: GridPoint,
+    pub kind: SelectionKind
+}
+
+impl Selection {
+    pub fn new(point: GridPoint, kind: SelectionKind) -> Self {
+        Self {
+            anchor: point,
+            end: point,
+            kind
+        }
+    }
+}
+
+impl Screen {
+    // Line 0 is the top of the screen when it was created.
+    pub fn get_grid_line(&self, line: isize) -> Option<Cow<'_, CellLine>> {
+        let row = line - self.pushed_to_history as isize;
+        
+        if row >= 0 {
+            self.screen_lines.get(row as usize).map(Cow::Borrowed)
+        } else {
+            self.history.get((-row - 1) as usize)
+                .map(|line| Cow::Owned(line.to_cell_line(self.line_cell_width)))
+        }
+    }
+    
+    // First and last cells of the selection (inclusive), once extended to whole words or lines.
+    // None if nothing is selected, which is the case of a simple click which wasn't dragged.
+    pub fn get_selection_range(&self, selection: &Selection) -> Option<(GridPoint, GridPoint)> {
+        let (start, end) = if selection.anchor <= selection.end {
+            (selection.anchor, selection.end)
+        } else {
+            (selection.end, selection.anchor)
+        };
+        
+        match selection.kind {
+            SelectionKind::Simple if start == end => None,
+            SelectionKind::Simple => Some((start, end)),
+            SelectionKind::Word => Some((self.get_word_start(start), self.get_word_end(end))),
+            SelectionKind::Line => Some((self.get_line_start(start), self.get_line_end(end)))
+        }
+    }
+    
+    // Text of the selection, with the lines which were wrapped joined back together, and without
+    // the blank cells at the end of the other ones.
+    pub fn get_selection_text(&self, selection: &Selection) -> Option<String> {
+        let (start, end) = self.get_selection_range(selection)?;
+        let mut text = String::new();
+        
+        for line_index in start.line..=end.line {
+            let line = match self.get_grid_line(line_index) {
+                Some(line) => line,
+                None => continue
+            };
+            
+            let first = if line_index == start.line { start.column } else { 0 };
+            let last = if line_index == end.line { end.column } else { line.cells.len() };
+            let last = (last + 1).min(line.cells.len());
+            
+            let mut line_text = String::new();
+            
+            for cell in line.cells.get(first..last).unwrap_or(&[]) {
+                match &cell.state {
+                    CellState::Filled(grapheme) => grapheme.push_to(&mut line_text),
+                    CellState::Empty => line_text.push(' '),
+                    CellState::Invalid => line_text.push('�'),
+                    CellState::Spacer => {}
+                }
+            }
+            
+            let ends_line = line_index != end.line && !line.wrapped;
+            
+            if !line.wrapped || line_index == end.line {
+                line_text.truncate(line_text.trim_end_matches(' ').len());
+            }
+            
+            text.push_str(&line_text);
+            
+            if ends_line {
+                text.push('\n');
+            }
+        }
+        
+        Some(text)
+    }
+    
+    fn is_word_cell(&self, line: &CellLine, column: usize) -> bool {
+        match line.cells.get(column).map(|cell| &cell.state) {
+            Some(CellState::Filled(grapheme)) => {
+                let base = grapheme.base();
+                !base.is_whitespace() && !WORD_SEPARATORS.contains(base)
+            },
+            // The right half of a wide character is part of the same word.
+            Some(CellState::Spacer) => true,
+            _ => false
+        }
+    }
+    
+    // A point outside of any word only selects itself.
+    fn get_word_start(&self, point: GridPoint) -> GridPoint {
+        let line = match self.get_grid_line(point.line) {
+            Some(line) => line,
+            None => return point
+        };
+        
+        let mut column = point.column;
+        
+        if self.is_word_cell(&line, column) {
+            while column > 0 && self.is_word_cell(&line, column - 1) {
+                column -= 1;
+            }
+        }
+        
+        GridPoint {
+            column,
+            ..point
+        }
+    }
+    
+    fn get_word_end(&self, point: GridPoint) -> GridPoint {
+        let line = match self.get_grid_line(point.line) {
+            Some(line) => line,
+            None => return point
+        };
+        
+        let mut column = point.column;
+        
+        if self.is_word_cell(&line, column) {
+            while self.is_word_cell(&line, column + 1) {
+                column += 1;
+            }
+        }
+        
+        GridPoint {
+            column,
+            ..point
+        }
+    }
+    
+    // Lines are extended to the lines wrapped with them.
+    fn get_line_start(&self, point: GridPoint) -> GridPoint {
+        let mut line = point.line;
+        
+        while self.get_grid_line(line - 1).map_or(false, |previous| previous.wrapped) {
+            line -= 1;
+        }
+        
+        GridPoint {
+            line,
+            column: 0
+        }
+    }
+    
+    fn get_line_end(&self, point: GridPoint) -> GridPoint {
+        let mut line = point.line;
+        
+        while self.get_grid_line(line).map_or(false, |current| current.wrapped)
+            && self.get_grid_line(line + 1).is_some() {
+            line += 1;
+        }
+        
+        GridPoint {
+            line,
+            column: self.line_cell_width.saturating_sub(1)
+        }
+    }
+}
@@ -1,10 +1,22 @@
 use super::*;
 
 impl Screen {
+    // The hyperlink isn't a graphic rendition, and stays until ended by OSC 8.
     pub fn reset_graphics(&mut self) {
-        self.cursor.properties = CellProperties::new();
+        self.cursor.properties = CellProperties {
+            hyperlink: self.cursor.properties.hyperlink,
+            ..CellProperties::new()
+        };
     }
 
+    pub fn set_attributes(&mut self, attributes: Attributes) {
+        self.cursor.properties.attributes.insert(attributes);
+    }
+    
+    pub fn reset_attributes(&mut self, attributes: Attributes) {
+        self.cursor.properties.attributes.remove(attributes);
+    }
+    
     // TODO: be able to configurable
     fn get_simple_color(&mut self, color: u8) -> Color {
         SimpleColor::from_u8(color).to_color()
@@ -17,10 +17,17 @@ use crate::pty_buffer::*;
 // Maybe going to need the font info too when multifont ?
 #[derive(Debug, Clone)]
 pub struct DisplayCell {
-    pub ftg: FreeTypeGlyph,
+    // Glyphs of the grapheme in the cell, drawn one after the other
+    pub glyphs: Vec<FreeTypeGlyph>,
     pub fg_color: Color,
     pub bg_color: Option<Color>,
+    pub attributes: Attributes,
+    // Whether the glyph spans two cells
+    pub wide: bool,
     pub is_cursor: bool,
+    pub selected: bool,
+    // Id of the link of the cell, underlined while hovered
+    pub hyperlink: Option<u32>,
 }
 
 // Contains a cell line, aka a line of cell to be rendered.
@@ -39,13 +46,149 @@ impl DisplayCellLine {
     }
 }
 
+// Font style of a run of cells, selected from their attributes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FontStyle {
+    Regular = 0,
+    Bold,
+    Italic,
+    BoldItalic
+}
 
-struct CLibsWrapper {
+impl FontStyle {
+    pub fn from_attributes(attributes: Attributes) -> Self {
+        let bold = attributes.contains(Attributes::BOLD);
+        let italic = attributes.contains(Attributes::ITALIC);
+        
+        match (bold, italic) {
+            (false, false) => FontStyle::Regular,
+            (true, false) => FontStyle::Bold,
+            (false, true) => FontStyle::Italic,
+            (true, true) => FontStyle::BoldItalic
+        }
+    }
+    
+    fn is_bold(self) -> bool {
+        self == FontStyle::Bold || self == FontStyle::BoldItalic
+    }
+    
+    fn is_italic(self) -> bool {
+        self == FontStyle::Italic || self == FontStyle::BoldItalic
+    }
+}
+
+// A font with both its Harfbuzz and FreeType handles.
+// When the font file of a style is missing, the handles of another style are reused, and FreeType
+// makes up the difference.
+#[derive(Copy, Clone)]
+struct FontFace {
     font: NonNull<hb_font_t>,
+    face: FT_Face,
+    synthetic: SyntheticStyle
+}
+
+impl FontFace {
+    fn load(lib: FT_Library, path: &str, size: i16) -> Self {
+        let font = NonNull::new(create_harfbuzz_font(path).unwrap()).unwrap();
+        let face = new_face(lib, path).unwrap();
+        set_char_size(face, size as i64).unwrap();
+        
+        Self {
+            font,
+            face,
+            synthetic: SyntheticStyle::default()
+        }
+    }
+    
+    // Load an optional font file, only if it exists.
+    fn load_if_exists(lib: FT_Library, path: &str, size: i16) -> Option<Self> {
+        if std::path::Path::new(path).is_file() {
+            Some(Self::load(lib, path, size))
+        } else {
+            println!("Font file {} not found, ignoring it", path);
+            None
+        }
+    }
+    
+    fn synthesized(self, bold: bool, italic: bool) -> Self {
+        Self {
+            synthetic: SyntheticStyle {
+                bold: self.synthetic.bold || bold,
+                italic: self.synthetic.italic || italic
+            },
+            ..self
+        }
+    }
+}
+
+struct CLibsWrapper {
     // Not sure how Pin works, so i'll recreate a pointer each time it's needed for now
     buffer: Buffer,
     lib: FT_Library,
-    face: FT_Face
+    // One face per FontStyle, in the same order
+    faces: [FontFace; 4],
+    // Faces used for the characters missing from the main ones, in order of preference
+    fallbacks: Vec<FontFace>
+}
+
+impl CLibsWrapper {
+    // Faces are numbered with 0 being the main face of the style, and the next ones the
+    // fallback faces, with the style applied by FreeType.
+    fn get_face(&self, index: usize, style: FontStyle) -> FontFace {
+        if index == 0 {
+            self.faces[style as usize]
+        } else {
+            self.fallbacks[index - 1].synthesized(style.is_bold(), style.is_italic())
+        }
+    }
+    
+    // Find the first face having a glyph for a character.
+    // If none has one, use the main face, which will draw its missing glyph.
+    fn find_face(&self, character: char, style: FontStyle) -> usize {
+        if has_glyph(self.faces[style as usize].face, character) {
+            return 0;
+        }
+        
+        self.fallbacks.iter()
+            .position(|fallback| has_glyph(fallback.face, character))
+            .map_or(0, |index| index + 1)
+    }
+    
+    // Shape the run of graphemes starting at the given byte offsets of the text and ending at
+    // `end`, and give back the glyphs of each grapheme. A grapheme may get no glyph, or several
+    // when the font has no precomposed one for it.
+    fn shape_and_render(&self, text: &str, graphemes: &[usize], end: usize, face_index: usize, style: FontStyle) -> Vec<Vec<FreeTypeGlyph>> {
+        let face = self.get_face(face_index, style);
+        let run_start = graphemes.first().copied().unwrap_or(end);
+        let text = &text[run_start..end];
+        let buffer = create_harfbuzz_buffer(text.len());
+        let buffer_p = buffer.as_ptr();
+        let (glyphs, clusters) = unsafe {
+            add_slice_to_buffer(buffer_p, text.as_bytes());
+            harfbuzz_shape(face.font.as_ptr(), buffer_p);
+            (get_buffer_glyph(buffer_p), get_buffer_clusters(buffer_p))
+        };
+        
+        // Each face and style combination renders different glyphs for the same glyph index.
+        let face_id = (face_index * 4) as u32 + style as u32;
+        
+        let rendered = render_glyphs(self.lib, face.face, face_id, &glyphs, face.synthetic).unwrap();
+        
+        let mut results: Vec<Vec<FreeTypeGlyph>> = vec![vec![]; graphemes.len()];
+        
+        for (glyph, cluster) in rendered.into_iter().zip(clusters) {
+            let index = match graphemes.binary_search(&(run_start + cluster as usize)) {
+                Ok(index) => index,
+                Err(index) => index.saturating_sub(1)
+            };
+            
+            if let Some(result) = results.get_mut(index) {
+                result.push(glyph);
+            }
+        }
+        
+        results
+    }
 }
 
 struct SendableCLibsWrapper(Arc<Mutex<CLibsWrapper>>);
@@ -65,18 +208,42 @@ pub type WrappedRasterizer = Arc<RwLock<Rasterizer>>;
 
 impl Rasterizer {
     pub fn new(config: Config, dimensions: RectSize) -> Self {
-        let hb_font = NonNull::new(create_harfbuzz_font(&config.font.path).unwrap()).unwrap();
         let buffer = create_harfbuzz_buffer(1);
         
         let freetype_lib = init_freetype().unwrap();
-        let face = new_face(freetype_lib, &config.font.path).unwrap();
-        set_char_size(face, config.font.size as i64).unwrap();
+        let size = config.font.size;
+        
+        let regular = FontFace::load(freetype_lib, &config.font.path, size);
+        let load_optional = |path: &Option<String>| {
+            path.as_ref().and_then(|path| FontFace::load_if_exists(freetype_lib, path, size))
+        };
+        
+        let bold = load_optional(&config.font.bold_path);
+        let italic = load_optional(&config.font.italic_path);
+        let bold_italic = load_optional(&config.font.bold_italic_path);
+        
+        // Without a bold italic font, start from the closest style available.
+        let bold_italic = bold_italic
+            .or_else(|| bold.map(|face| face.synthesized(false, true)))
+            .or_else(|| italic.map(|face| face.synthesized(true, false)))
+            .unwrap_or_else(|| regular.synthesized(true, true));
+        
+        let faces = [
+            regular,
+            bold.unwrap_or_else(|| regular.synthesized(true, false)),
+            italic.unwrap_or_else(|| regular.synthesized(false, true)),
+            bold_italic
+        ];
+        
+        let fallbacks = config.font.fallback_paths.iter()
+            .filter_map(|path| FontFace::load_if_exists(freetype_lib, path, size))
+            .collect();
         
         let wrapper = SendableCLibsWrapper(Arc::new(Mutex::new(CLibsWrapper {
-            font: hb_font,
             buffer,
             lib: freetype_lib,
-            face
+            faces,
+            fallbacks
         })));
         
         let cell_size = RectSize {
@@ -97,16 +264,54 @@ impl Rasterizer {
         rasterizer
     }  
     
-    pub fn rasterize(&mut self, characters: &[u8]) -> Vec<FreeTypeGlyph> {
+    // Graphemes start at the given byte offsets of the text, and get back their glyphs.
+    // They are split in runs covered by the same face, each run being shaped on its own.
+    pub fn rasterize(&mut self, text: &str, graphemes: &[usize], style: FontStyle) -> Vec<Vec<FreeTypeGlyph>> {
         let handle = self.wrapper.0.lock().unwrap();
-        let buffer = create_harfbuzz_buffer(characters.len());
-        let buffer_p = buffer.as_ptr();
-        let glyphs = unsafe {
-            add_slice_to_buffer(buffer_p, characters);
-            harfbuzz_shape(handle.font.as_ptr(), buffer_p);
-            get_buffer_glyph(buffer_p)
-        };
-        render_glyphs(handle.lib, handle.face, &glyphs).unwrap()
+        
+        let mut glyphs = Vec::<Vec<FreeTypeGlyph>>::with_capacity(graphemes.len());
+        let mut run_start = 0usize;
+        let mut run_face: Option<usize> = None;
+        
+        // A grapheme is drawn by the face having its base character.
+        for (index, &start) in graphemes.iter().enumerate() {
+            let character = text[start..].chars().next().unwrap_or(' ');
+            let face = handle.find_face(character, style);
+            
+            match run_face {
+                Some(current) if current != face => {
+                    glyphs.append(&mut handle.shape_and_render(text, &graphemes[run_start..index], start, current, style));
+                    run_start = index;
+                    run_face = Some(face);
+                },
+                Some(_) => {},
+                None => run_face = Some(face)
+            }
+        }
+        
+        if let Some(current) = run_face {
+            glyphs.append(&mut handle.shape_and_render(text, &graphemes[run_start..], text.len(), current, style));
+        }
+        
+        glyphs
+    }
+    
+    pub fn get_font_size(&self) -> i16 {
+        self.config.font.size
+    }
+    
+    // Every face is resized, and the cells with them.
+    pub fn set_font_size(&mut self, size: i16) {
+        {
+            let handle = self.wrapper.0.lock().unwrap();
+            
+            for face in handle.faces.iter().chain(handle.fallbacks.iter()) {
+                set_char_size(face.face, size as i64).unwrap();
+            }
+        }
+        
+        self.config.font.size = size;
+        self.guess_cell_size();
     }
     
     // update the dimensions of the drawer.
@@ -128,7 +333,12 @@ impl Rasterizer {
     }
     
     fn guess_cell_size(&mut self) {
-        let rasterized = self.rasterize("▀  ▄  █".as_bytes());
+        let text = "▀  ▄  █";
+        let graphemes: Vec<usize> = text.char_indices().map(|(index, _)| index).collect();
+        let rasterized: Vec<FreeTypeGlyph> = self.rasterize(text, &graphemes, FontStyle::Regular)
+            .into_iter()
+            .flatten()
+            .collect();
         
         let mut current_width: i64 = 0;
         let mut current_height: i64 = 0;
@@ -211,17 +421,41 @@ impl Rasterizer {
         
         let mut display_cell_lines = Vec::<DisplayCellLine>::new();
         
-        let mut to_rasterize = String::with_capacity(cells.len());
+        // Spacers of wide characters aren't drawn, the cell of their character spans them.
+        let cells: Vec<Cell> = cells.iter()
+            .filter(|cell| !cell.state.is_spacer())
+            .cloned()
+            .collect();
+        
+        let mut rasterized = Vec::<Vec<FreeTypeGlyph>>::with_capacity(cells.len());
+        
+        // Consecutive cells with the same font style are shaped together.
+        let mut run_start = 0usize;
         
-        for cell in cells.iter() {
-            match cell.state {
-                CellState::Filled(content) => to_rasterize.push(content),
-                CellState::Empty => to_rasterize.push(' '),
-                CellState::Invalid | CellState::Filling(_) => to_rasterize.push('�')
+        while run_start < cells.len() {
+            let style = FontStyle::from_attributes(cells[run_start].properties.attributes);
+            
+            let run_end = cells[run_start..].iter()
+                .position(|cell| FontStyle::from_attributes(cell.properties.attributes) != style)
+                .map_or(cells.len(), |length| run_start + length);
+            
+            let mut to_rasterize = String::with_capacity(run_end - run_start);
+            let mut graphemes = Vec::<usize>::with_capacity(run_end - run_start);
+            
+            for cell in cells[run_start..run_end].iter() {
+                graphemes.push(to_rasterize.len());
+                
+                match &cell.state {
+                    CellState::Filled(grapheme) => grapheme.push_to(&mut to_rasterize),
+                    CellState::Empty | CellState::Spacer => to_rasterize.push(' '),
+                    CellState::Invalid => to_rasterize.push('�')
+                }
             }
+            
+            rasterized.append(&mut self.rasterize(&to_rasterize, &graphemes, style));
+            run_start = run_end;
         }
         
-        let mut rasterized = self.rasterize(to_rasterize.as_bytes());
         let mut cell_index = 0usize;
         loop {
             if rasterized.is_empty() {
@@ -235,13 +469,17 @@ impl Rasterizer {
             };
             
             let drain = rasterized.drain(0..number_to_remove);
-            let cells: Vec<DisplayCell> = drain.map(|ftg| {    
-                let cell = cells[cell_index];
+            let cells: Vec<DisplayCell> = drain.map(|glyphs| {    
+                let cell = &cells[cell_index];
                 let display = DisplayCell {
-                    ftg,
+                    glyphs,
                     fg_color: cell.properties.fg,
                     bg_color: cell.properties.bg,
-                    is_cursor: false
+                    attributes: cell.properties.attributes,
+                    wide: cell.state.is_wide(),
+                    is_cursor: false,
+                    selected: false,
+                    hyperlink: cell.properties.hyperlink
                 };
                 cell_index += 1;
                 display
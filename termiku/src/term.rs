@@ -9,8 +9,10 @@ use mio::{Events, Poll, PollOpt, Ready, Token, unix::EventedFd};
 use mio_extras::channel::{channel, Sender};
 
 use crate::config::*;
+use crate::control::base64;
+use crate::control::control_type::ClipboardTarget;
 use crate::pty::{self, PtyWithProcess};
-use crate::pty_buffer::{event::*, PtyBuffer};
+use crate::pty_buffer::{event::*, CursorStyle, MouseTracking, PtyBuffer, SelectionKind};
 use crate::rasterizer::*;
 use crate::window_event::*;
 use crate::youtube::*;
@@ -41,22 +43,57 @@ pub struct Term {
     
     pub youtube: Option<WrappedYoutubeDlVlcInstance>,
     
+    /// Title set by the program running in the terminal, empty if none was set.
+    pub title: String,
+    
+    /// Input the pty couldn't take yet, written when it becomes writable again.
+    pub pending_input: Vec<u8>,
+    
     /*
     /// We may want to implement visual bells (\a / 0x07 / ^G), like flashing the tab.
     alerted: bool,
-    /// We probably want to implement terminal title setting on way or another.
-    title: String,
     */
    
    pub to_remove: bool,
 }
 
+impl Term {
+    // Write as much of the pending input as the pty takes without blocking, the rest waits for the
+    // next writable event of the pty.
+    pub fn flush_pending_input(&mut self) {
+        while !self.pending_input.is_empty() {
+            match self.pty.pty.write(&self.pending_input) {
+                Ok(0) => break,
+                Ok(amount) => {
+                    self.pending_input.drain(..amount);
+                },
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => {},
+                Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    println!("Couldn't write to the pty: {}", error);
+                    self.pending_input.clear();
+                }
+            }
+        }
+    }
+}
+
 type WrappedTermList = Arc<RwLock<TermList>>;
 
+/// Access to the clipboard asked by a program, which the window carries out.
+#[derive(Clone, Debug)]
+pub enum ClipboardRequest {
+    Set(ClipboardTarget, String),
+    Query(ClipboardTarget)
+}
+
 struct TermList {
     inner: Vec<Term>,
     active_uid: usize,
     
+    /// Clipboard requests not taken by the window yet, with the uid of the terminal asking
+    clipboard_requests: Vec<(usize, ClipboardRequest)>,
+    
     char_buffer: [u8; 4]
 }
 
@@ -66,6 +103,8 @@ impl TermList {
             inner: vec![],
             active_uid: FIRST_TERMINAL_UID,
             
+            clipboard_requests: vec![],
+            
             char_buffer: [0; 4]
         }
     }
@@ -79,6 +118,28 @@ impl TermList {
         self.inner.push(term);
     }
     
+    // The newly active terminal is drawn even if nothing changed inside it.
+    pub fn make_active(&mut self, uid: usize) {
+        self.active_uid = uid;
+        
+        if let Some(term) = self.get_active_mut() {
+            term.buffer.mark_updated();
+        }
+    }
+    
+    // Make the terminal `offset` places after the active one active, wrapping around.
+    pub fn cycle_active(&mut self, offset: isize) {
+        if self.inner.is_empty() {
+            return;
+        }
+        
+        let length = self.inner.len() as isize;
+        let index = self.find_index(self.active_uid).unwrap_or(0) as isize;
+        let new_index = (index + offset).rem_euclid(length) as usize;
+        
+        self.make_active(self.inner[new_index].uid);
+    }
+    
     pub fn find_index(&self, uid: usize) -> Option<usize> {
         self.inner.iter().position(|el| { el.uid == uid
         })
@@ -115,9 +176,11 @@ impl TermList {
         self.get_uid_mut(self.active_uid)
     }
     
+    // The input is queued behind what the pty couldn't take yet, so it's written in order.
     pub fn write_buffer_to_pty(&mut self, buffer: &[u8], index: usize) {
         if let Some(term) = self.get_mut(index) {
-            term.pty.pty.write_all(buffer).unwrap();
+            term.pending_input.extend_from_slice(buffer);
+            term.flush_pending_input();
         }
     }
     
@@ -144,6 +207,13 @@ impl TermList {
         
         self.inner.retain(|term| !term.to_remove);
         
+        // When the active terminal exits, the last one takes its place.
+        if self.find_index(self.active_uid).is_none() {
+            if let Some(uid) = self.inner.last().map(|term| term.uid) {
+                self.make_active(uid);
+            }
+        }
+        
         self.inner.len()
     }
 }
@@ -243,6 +313,15 @@ impl TermManager {
                     } else {
                         let uid = event.token().0;
                         
+                        if event.readiness().is_writable() {
+                            // Should panic if poisoned.
+                            let mut handle = cloned_termlist.write().unwrap();
+                            
+                            if let Some(term) = handle.get_uid_mut(uid) {
+                                term.flush_pending_input();
+                            }
+                        }
+                        
                         if event.readiness().is_readable() {
                             // Should panic if poisoned.
                             let mut handle = cloned_termlist.write().unwrap();
@@ -300,15 +379,28 @@ impl TermManager {
     pub fn add_new_term(&mut self) {
         let term = self.factory.make_term();
         
-        self.poll.register(&term.pty, Token(term.uid), Ready::readable(), PollOpt::edge()).unwrap();
+        // Writable events let the input the pty couldn't take right away be written later.
+        self.poll.register(&term.pty, Token(term.uid), Ready::readable() | Ready::writable(), PollOpt::edge()).unwrap();
         
         {
             // Should panic if poisoned.
             let mut list = self.list.write().unwrap(); 
+            let uid = term.uid;
             list.push(term);
+            list.make_active(uid);
         }
     }
     
+    pub fn activate_next_term(&mut self) {
+        // Should panic if poisoned.
+        self.list.write().unwrap().cycle_active(1);
+    }
+    
+    pub fn activate_previous_term(&mut self) {
+        // Should panic if poisoned.
+        self.list.write().unwrap().cycle_active(-1);
+    }
+    
     pub fn send_event(&mut self, event: TermikuWindowEvent) {
         // Should panic if poisoned.
         self.window_sender.send(event).unwrap();
@@ -358,6 +450,67 @@ impl TermManager {
         }
     }
     
+    pub fn get_title_from_active(&self) -> Option<String> {
+        // Should panic if poisoned.
+        let list = self.list.read().unwrap();
+        
+        list.get_active().map(|term| term.title.clone())
+    }
+    
+    // Requests of every terminal, not only the active one.
+    pub fn take_clipboard_requests(&mut self) -> Vec<(usize, ClipboardRequest)> {
+        // Should panic if poisoned.
+        let mut list = self.list.write().unwrap();
+        
+        std::mem::replace(&mut list.clipboard_requests, vec![])
+    }
+    
+    pub fn get_hyperlink_from_active(&self, column: usize, row: usize) -> Option<(u32, String)> {
+        // Should panic if poisoned.
+        let list = self.list.read().unwrap();
+        
+        list.get_active().and_then(|term| term.buffer.get_hyperlink(column, row))
+    }
+    
+    pub fn get_cursor_style_from_active(&self) -> Option<CursorStyle> {
+        // Should panic if poisoned.
+        let list = self.list.read().unwrap();
+        
+        list.get_active().and_then(|term| term.buffer.get_cursor_style())
+    }
+    
+    pub fn get_mouse_tracking_from_active(&self) -> MouseTracking {
+        // Should panic if poisoned.
+        let list = self.list.read().unwrap();
+        
+        list.get_active().map_or(MouseTracking::None, |term| term.buffer.get_state().mouse_tracking)
+    }
+    
+    pub fn start_selection_on_active(&mut self, column: usize, row: usize, kind: SelectionKind) {
+        // Should panic if poisoned.
+        let mut list = self.list.write().unwrap();
+        
+        if let Some(term) = list.get_active_mut() {
+            term.buffer.start_selection(column, row, kind);
+        }
+    }
+    
+    pub fn extend_selection_on_active(&mut self, column: usize, row: usize) {
+        // Should panic if poisoned.
+        let mut list = self.list.write().unwrap();
+        
+        if let Some(term) = list.get_active_mut() {
+            term.buffer.extend_selection(column, row);
+        }
+    }
+    
+    pub fn get_selection_text_from_active(&self) -> Option<String> {
+        // Should panic if poisoned.
+        let list = self.list.read().unwrap();
+        
+        list.get_active().and_then(|term| term.buffer.get_selection_text())
+    }
+    
     pub fn is_active_updated(&self) -> bool {
         // Should panic if poisoned.
         let list = self.list.read().unwrap();
@@ -368,15 +521,39 @@ impl TermManager {
         }
     }
     
+    /// Propagate new window dimensions to every terminal: the pty gets its new size (which makes
+    /// the child receive a SIGWINCH), and the buffer resizes its screen accordingly.
+    /// The rasterizer must have been updated beforehand.
     pub fn dimensions_updated(&mut self) {
+        let winsize = self.factory.rasterizer.read().unwrap().get_winsize();
+
         // Should panic if poisoned.
         let mut list = self.list.write().unwrap();
 
         for term in list.inner.iter_mut() {
+            // Shouldn't panic, the child may have exited and not been cleaned up yet.
+            if let Err(error) = term.pty.pty.resize(winsize) {
+                println!("Error resizing pty {}: {}", term.uid, error);
+            }
+
             term.buffer.dimensions_updated();
         }
     }
     
+    /// Rasterize every terminal again after the font size changed. The cells' size changes along
+    /// with it, which is propagated like new window dimensions.
+    /// The rasterizer must have been updated beforehand.
+    pub fn font_updated(&mut self) {
+        self.dimensions_updated();
+        
+        // Should panic if poisoned.
+        let mut list = self.list.write().unwrap();
+        
+        for term in list.inner.iter_mut() {
+            term.buffer.font_updated();
+        }
+    }
+    
     /// Cleanup every exited terminals.
     /// Return if the window should exit (i.e. there's no more terminals to display).
     pub fn cleanup_exited_terminals(&mut self) -> bool {
@@ -419,12 +596,14 @@ impl TermFactory {
             self.rasterizer.read().unwrap().get_winsize()
         ).unwrap();
         
-        let buffer = PtyBuffer::new(self.rasterizer.clone(), self.sender.clone(), self.count);
+        let buffer = PtyBuffer::new(self.config.clone(), self.rasterizer.clone(), self.sender.clone(), self.count);
 
         
         let term = Term {
             pty,
             youtube: None,
+            title: String::new(),
+            pending_input: vec![],
             buffer,
             uid: self.count,
             to_remove: false,
@@ -438,9 +617,37 @@ impl TermFactory {
 fn handle_window_event(event: TermikuWindowEvent, termlist: &mut TermList, char_buffer: &mut [u8]) {
     use TermikuWindowEvent::*;
     
+    let state = termlist.get_active().map(|term| term.buffer.get_state()).unwrap_or_default();
+    
     match event {
         CharacterInput(character) => termlist.write_buffer_to_active_pty(character.encode_utf8(char_buffer).as_bytes()),
-        KeyboardArrow(arrow) => termlist.write_buffer_to_active_pty(arrow.to_control_sequence().as_bytes()),
+        AltCharacterInput(character) => {
+            let mut input = vec![0x1B];
+            input.extend(character.encode_utf8(char_buffer).as_bytes());
+            termlist.write_buffer_to_active_pty(&input);
+        }
+        StringInput(input) => termlist.write_buffer_to_active_pty(input.as_bytes()),
+        Paste(text) => termlist.write_buffer_to_active_pty(&get_paste_sequence(&text, state.bracketed_paste)),
+        KeyInput(key) => termlist.write_buffer_to_active_pty(&key.to_control_sequence(&state)),
+        // Tracking may have been turned off since the window checked it.
+        Mouse(report) => if state.mouse_tracking != MouseTracking::None {
+            if let Some(sequence) = report.to_control_sequence(state.mouse_encoding) {
+                termlist.write_buffer_to_active_pty(&sequence);
+            }
+        },
+        Scroll(scroll) => if let Some(term) = termlist.get_active_mut() {
+            term.buffer.scroll(scroll);
+        },
+        // Replied like xterm, with the contents in base64.
+        ClipboardContents(terminal_id, target, contents) => {
+            let name = match target {
+                ClipboardTarget::Clipboard => 'c',
+                ClipboardTarget::Primary => 'p'
+            };
+            
+            let reply = format!("\x1b]52;{};{}\x1b\\", name, base64::encode(contents.as_bytes()));
+            termlist.write_buffer_to_uid_pty(reply.as_bytes(), terminal_id);
+        }
     }
 }
 
@@ -461,6 +668,18 @@ fn handle_screen_event(event: ScreenEvent, termlist: &mut TermList) {
                     WrappedYoutubeDlVlcInstance::new(ytdl)
                 )
             }
-        }
+        },
+        SetTitle(title) => {
+            if let Some(term) = termlist.get_uid_mut(event.terminal_id) {
+                term.title = title;
+            }
+        },
+        SetClipboard(target, contents) => {
+            termlist.clipboard_requests.push((event.terminal_id, ClipboardRequest::Set(target, contents)));
+        },
+        QueryClipboard(target) => {
+            termlist.clipboard_requests.push((event.terminal_id, ClipboardRequest::Query(target)));
+        },
+        Reply(reply) => termlist.write_buffer_to_uid_pty(&reply, event.terminal_id)
     }
 }
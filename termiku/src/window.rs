@@ -1,29 +1,40 @@
 // A good lot of this code is taken from glium/examples/image.rs
 // For now, we only want a window capable of receiving keyboard inputs as a basis for future work
+use std::process::Command;
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant, SystemTime};
 
 use glium::{glutin, Display, Surface};
-use glium::glutin::event::{Event, StartCause, WindowEvent};
+use glium::glutin::event::{ElementState, Event, ModifiersState, StartCause, WindowEvent};
+use glium::glutin::event::MouseButton as WindowMouseButton;
 use glium::glutin::event_loop::{ControlFlow, EventLoop};
 use glium::index::PrimitiveType;
 
 use mio_extras::channel::Sender;
 
 use crate::atlas::RectSize;
+use crate::clipboard::Clipboard;
 use crate::config::*;
 use crate::draw::*;
+use crate::pty_buffer::MouseTracking;
 use crate::rasterizer::*;
 use crate::term::*;
 use crate::window_event::*;
 
 pub const DEFAULT_BG: (f32, f32, f32, f32) = (0.0, 0.0, 0.0, 0.5);
 
+// Title of the window when the active terminal didn't set one.
+const DEFAULT_TITLE: &str = "mou ikkai";
+
+// Font size changes of the FontSizeUp and FontSizeDown actions, and the smallest size they reach
+const FONT_SIZE_STEP: i16 = 1;
+const MIN_FONT_SIZE: i16 = 6;
+
 pub fn window(config: Config) {    
     let events_loop = EventLoop::new();
     let window_builder = glutin::window::WindowBuilder::new()
         .with_inner_size(glutin::dpi::LogicalSize::new(1280.0, 720.0))
-        .with_title("mou ikkai")
+        .with_title(DEFAULT_TITLE)
         .with_transparent(config.transparent);
     let context_builder = glutin::ContextBuilder::new();
     
@@ -97,19 +108,37 @@ pub fn window(config: Config) {
 
     let mut drawer = Drawer::new(&display, config.clone());
     let rasterizer = Arc::new(RwLock::new(Rasterizer::new(config.clone(), get_display_size(&display))));
-    let cell_size = rasterizer.read().unwrap().cell_size;
-    let delta_cell_height = rasterizer.read().unwrap().delta_cell_height;
+    let mut cell_size = rasterizer.read().unwrap().cell_size;
+    let mut delta_cell_height = rasterizer.read().unwrap().delta_cell_height;
 
     let mut manager = TermManager::new(config.clone(), rasterizer.clone());
     let mut dimensions = get_display_size(&display); 
-    let mut lines = manager.get_lines_from_active_force(0, 20);
+    let mut lines = manager.get_lines_from_active_force(0, rasterizer.read().unwrap().get_line_cell_height() as usize);
     let mut first_draw = true;
     
     
     let mut old = SystemTime::now();
     let mut t: u128 = 0;
     
-    let mut old_display_cursor = false;
+    let mut old_cursor: Option<CursorDrawing> = None;
+    let mut focused = true;
+    
+    // Modifiers of the last key, used for the characters it types
+    let mut modifiers = ModifiersState::default();
+    // Whether the last key pressed was sent as a key input, in which case the character it also
+    // types, if any, isn't sent
+    let mut key_sent = false;
+    
+    let mut title = String::from(DEFAULT_TITLE);
+    
+    let mut clipboard = Clipboard::new();
+    let mut mouse = MouseState::default();
+    // Whether the left button is held to select text
+    let mut selecting = false;
+    // Whether the mouse is over the window, where it may hover a link
+    let mut mouse_inside = false;
+    // Cell for which the hovered link was last looked up
+    let mut hovered_cell: Option<(usize, usize)> = None;
     
     let mut display_cursor_t_base = 0u128;
     
@@ -130,9 +159,18 @@ pub fn window(config: Config) {
             display_cursor_t_base = t;
         }
         
-        let display_cursor = new_cursor_state(t - display_cursor_t_base);
-        need_refresh = need_refresh || (old_display_cursor ^ display_cursor);
-        old_display_cursor = display_cursor;
+        // Steady cursors, and the hollow cursor of an unfocused window, don't blink.
+        let cursor = manager.get_cursor_style_from_active().and_then(|style| {
+            if !focused {
+                Some(CursorDrawing::Hollow)
+            } else if !style.blinking || new_cursor_state(t - display_cursor_t_base) {
+                Some(CursorDrawing::Shape(style.shape))
+            } else {
+                None
+            }
+        });
+        need_refresh = need_refresh || (old_cursor != cursor);
+        old_cursor = cursor;
         
         if first_draw {
             first_draw = false;
@@ -147,12 +185,57 @@ pub fn window(config: Config) {
             manager.dimensions_updated();
         }
         
-        let maybe_new = manager.get_lines_from_active(0, 40);
+        let new_title = match manager.get_title_from_active() {
+            Some(new_title) if !new_title.is_empty() => new_title,
+            _ => String::from(DEFAULT_TITLE)
+        };
+        
+        if new_title != title {
+            display.gl_window().window().set_title(&new_title);
+            title = new_title;
+        }
+        
+        for (terminal_id, request) in manager.take_clipboard_requests() {
+            match request {
+                ClipboardRequest::Set(target, contents) if config.clipboard_access.can_write() => {
+                    clipboard.set_target_contents(target, contents);
+                },
+                // Queries which aren't allowed are left unanswered.
+                ClipboardRequest::Query(target) if config.clipboard_access.can_read() => {
+                    let contents = clipboard.get_target_contents(target).unwrap_or_default();
+                    manager.send_event(TermikuWindowEvent::ClipboardContents(terminal_id, target, contents));
+                },
+                _ => {}
+            }
+        }
+        
+        let line_cell_height = rasterizer.read().unwrap().get_line_cell_height() as usize;
+        let maybe_new = manager.get_lines_from_active(0, line_cell_height);
+        let lines_updated = maybe_new.is_some();
         if let Some(new_lines) = maybe_new {
             lines = new_lines;
             need_refresh = true;
         }
         
+        // The link under the mouse changes with it, and with the contents of the terminal.
+        let mouse_cell = if mouse_inside {
+            Some(mouse.get_cell())
+        } else {
+            None
+        };
+        
+        if mouse_cell != hovered_cell || lines_updated {
+            hovered_cell = mouse_cell;
+            
+            let hovered_hyperlink = mouse_cell.and_then(|(column, row)| {
+                manager.get_hyperlink_from_active(column, row).map(|(id, _)| id)
+            });
+            
+            if drawer.set_hovered_hyperlink(hovered_hyperlink) {
+                need_refresh = true;
+            }
+        }
+        
         if let Some(new_frame) = manager.get_youtube_frame_from_active() {
             frame = new_frame;
             need_refresh = true;
@@ -196,23 +279,128 @@ pub fn window(config: Config) {
                 .unwrap();
             }
             
-            drawer.render_lines(&lines, display_cursor, cell_size, delta_cell_height, &display, &mut target);
+            drawer.render_lines(&lines, cursor, cell_size, delta_cell_height, &display, &mut target);
             
             target.finish().unwrap();
         }
         
 
         let mut action = Action::Continue;        
+        let mut font_size_change = 0;
+        
         for event in events {
             if let Event::WindowEvent { event, .. } = event {
                 match event {
                     WindowEvent::CloseRequested => action = Action::Stop,
+                    WindowEvent::Focused(is_focused) => focused = *is_focused,
                     WindowEvent::ReceivedCharacter(input) => {
-                        manager.send_event(TermikuWindowEvent::CharacterInput(*input))
+                        if key_sent {
+                            key_sent = false;
+                        } else {
+                            manager.send_event(handle_received_character(*input, modifiers));
+                        }
                     }
                     WindowEvent::KeyboardInput { input, .. } => {
                         // println!("{:?}", input);
-                        if let Some(event) = handle_keyboard_input(input) {
+                        modifiers = input.modifiers;
+                        
+                        // Bound keys are resolved before anything is sent to the terminal.
+                        if let Some(key_action) = find_key_binding(&config.keybindings, input) {
+                            key_sent = true;
+                            
+                            match key_action {
+                                KeyAction::Copy => if let Some(text) = manager.get_selection_text_from_active() {
+                                    clipboard.set_contents(text);
+                                },
+                                KeyAction::Paste => if let Some(contents) = clipboard.get_contents() {
+                                    manager.send_event(TermikuWindowEvent::Paste(contents));
+                                },
+                                KeyAction::NewTab => manager.add_new_term(),
+                                KeyAction::NextTab => manager.activate_next_term(),
+                                KeyAction::PreviousTab => manager.activate_previous_term(),
+                                KeyAction::ScrollUp => manager.send_event(TermikuWindowEvent::Scroll(ScrollView::Up(1))),
+                                KeyAction::ScrollDown => manager.send_event(TermikuWindowEvent::Scroll(ScrollView::Down(1))),
+                                KeyAction::ScrollPageUp => manager.send_event(TermikuWindowEvent::Scroll(ScrollView::PageUp)),
+                                KeyAction::ScrollPageDown => manager.send_event(TermikuWindowEvent::Scroll(ScrollView::PageDown)),
+                                KeyAction::FontSizeUp => font_size_change += FONT_SIZE_STEP,
+                                KeyAction::FontSizeDown => font_size_change -= FONT_SIZE_STEP,
+                                KeyAction::SendString(string) => {
+                                    manager.send_event(TermikuWindowEvent::StringInput(string.clone()))
+                                }
+                            }
+                            
+                            continue;
+                        }
+                        
+                        let event = handle_keyboard_input(input);
+                        
+                        if pressed(input) {
+                            key_sent = event.is_some();
+                        }
+                        
+                        if let Some(event) = event {
+                            manager.send_event(event);
+                        }
+                    }
+                    WindowEvent::CursorEntered { .. } => mouse_inside = true,
+                    WindowEvent::CursorLeft { .. } => mouse_inside = false,
+                    WindowEvent::CursorMoved { position, modifiers, .. } => {
+                        mouse_inside = true;
+                        
+                        let tracking = get_reported_mouse_tracking(&manager, *modifiers);
+                        
+                        if let Some(event) = mouse.moved(position.x, position.y, cell_size, delta_cell_height, tracking, *modifiers) {
+                            manager.send_event(event);
+                        }
+                        
+                        if selecting {
+                            let (column, row) = mouse.get_cell();
+                            manager.extend_selection_on_active(column, row);
+                        }
+                    }
+                    WindowEvent::MouseInput { state, button, modifiers, .. } => {
+                        // Ctrl+click opens links, and is neither reported nor starts a selection.
+                        if modifiers.ctrl && *button == WindowMouseButton::Left {
+                            let (column, row) = mouse.get_cell();
+                            
+                            if let Some((_, uri)) = manager.get_hyperlink_from_active(column, row) {
+                                if *state == ElementState::Pressed {
+                                    open_hyperlink(&config.hyperlinks, &uri);
+                                }
+                                
+                                continue;
+                            }
+                        }
+                        
+                        let tracking = get_reported_mouse_tracking(&manager, *modifiers);
+                        
+                        if let Some(event) = mouse.input(*state, *button, tracking, *modifiers) {
+                            manager.send_event(event);
+                        } else if *button == WindowMouseButton::Left {
+                            match state {
+                                ElementState::Pressed => {
+                                    let (column, row) = mouse.get_cell();
+                                    let kind = mouse.click();
+                                    
+                                    manager.start_selection_on_active(column, row, kind);
+                                    selecting = true;
+                                },
+                                ElementState::Released if selecting => {
+                                    selecting = false;
+                                    
+                                    if let Some(text) = manager.get_selection_text_from_active() {
+                                        clipboard.set_contents(text.clone());
+                                        clipboard.set_primary_contents(text);
+                                    }
+                                },
+                                ElementState::Released => {}
+                            }
+                        }
+                    }
+                    WindowEvent::MouseWheel { delta, modifiers, .. } => {
+                        let tracking = get_reported_mouse_tracking(&manager, *modifiers);
+                        
+                        for event in mouse.wheel(delta, cell_size.height, tracking, *modifiers) {
                             manager.send_event(event);
                         }
                     }
@@ -221,6 +409,21 @@ pub fn window(config: Config) {
             }
         }
 
+        // Glyphs of the old size are dropped from the atlas, as they keep their ids.
+        if font_size_change != 0 {
+            {
+                let mut rasterizer = rasterizer.write().unwrap();
+                let size = (rasterizer.get_font_size() + font_size_change).max(MIN_FONT_SIZE);
+                
+                rasterizer.set_font_size(size);
+                cell_size = rasterizer.cell_size;
+                delta_cell_height = rasterizer.delta_cell_height;
+            }
+            
+            drawer.atlas.clear();
+            manager.font_updated();
+        }
+        
         action
     });
 }
@@ -298,3 +501,47 @@ fn get_time_diff(old: &mut SystemTime) -> u128 {
 fn new_cursor_state(t: u128) -> bool {
     (t % 1000) <= 500
 }
+
+// The process is waited for on its own thread, so that it doesn't stay a zombie.
+fn open_hyperlink(config: &HyperlinkConfig, uri: &str) {
+    if !is_openable_hyperlink(uri) {
+        println!("Not opening {}, its scheme isn't allowed", uri);
+        return;
+    }
+    
+    match Command::new(&config.program).args(&config.args).arg(uri).spawn() {
+        Ok(mut child) => {
+            std::thread::spawn(move || child.wait());
+        },
+        Err(error) => println!("Couldn't open {} with {}: {}", uri, config.program, error)
+    }
+}
+
+// Links come from the programs, so only web pages, paths and mail addresses are opened. A URI
+// starting with '-' would be taken as an option of the command.
+fn is_openable_hyperlink(uri: &str) -> bool {
+    if uri.starts_with('-') {
+        return false;
+    }
+    
+    let (scheme, rest) = match uri.find(':') {
+        Some(index) => (uri[..index].to_ascii_lowercase(), &uri[index + 1..]),
+        None => return false
+    };
+    
+    match scheme.as_str() {
+        "http" | "https" => rest.starts_with("//"),
+        "file" => rest.starts_with('/'),
+        "mailto" => !rest.is_empty(),
+        _ => false
+    }
+}
+
+// Shift bypasses mouse reporting, so that text can still be selected.
+fn get_reported_mouse_tracking(manager: &TermManager, modifiers: ModifiersState) -> MouseTracking {
+    if modifiers.shift {
+        MouseTracking::None
+    } else {
+        manager.get_mouse_tracking_from_active()
+    }
+}
@@ -1,62 +1,621 @@
-use glium::glutin::event::{ElementState, KeyboardInput, VirtualKeyCode };
+use glium::glutin::event::{ElementState, KeyboardInput, ModifiersState, MouseScrollDelta, VirtualKeyCode };
+use glium::glutin::event::MouseButton as WindowMouseButton;
 
-const UP_CONTROL_SEQUENCE:      &str = "\x1B[A";
-const DOWN_CONTROL_SEQUENCE:    &str = "\x1B[B";
-const RIGHT_CONTROL_SEQUENCE:   &str = "\x1B[C";
-const LEFT_CONTROL_SEQUENCE:    &str = "\x1B[D";
+use std::time::{Duration, Instant};
 
-#[derive(Copy, Clone, Debug)]
+use crate::atlas::RectSize;
+use crate::config::{KeyAction, KeyBinding, KeyModifier};
+use crate::control::control_type::ClipboardTarget;
+use crate::pty_buffer::{MouseEncoding, MouseTracking, ScreenState, SelectionKind};
+
+const ESCAPE: u8 = 0x1B;
+const DELETE: u8 = 0x7F;
+const BACKSPACE: u8 = 0x08;
+
+// Final bytes of the cursor keys, of Home and End: sent after CSI, or after SS3 with DECCKM
+const UP_FINAL_BYTE:    u8 = b'A';
+const DOWN_FINAL_BYTE:  u8 = b'B';
+const RIGHT_FINAL_BYTE: u8 = b'C';
+const LEFT_FINAL_BYTE:  u8 = b'D';
+const HOME_FINAL_BYTE:  u8 = b'H';
+const END_FINAL_BYTE:   u8 = b'F';
+
+// Final byte of the keypad Enter, sent after SS3 in application mode
+const KEYPAD_ENTER_FINAL_BYTE: u8 = b'M';
+
+// Parameters of the keys sent as CSI Pn ~
+const INSERT_NUMBER:    u16 = 2;
+const DELETE_NUMBER:    u16 = 3;
+const PAGE_UP_NUMBER:   u16 = 5;
+const PAGE_DOWN_NUMBER: u16 = 6;
+
+// F1 to F4 are sent as SS3 P to SS3 S, the other ones as CSI Pn ~, with gaps between the numbers
+const FUNCTION_FINAL_BYTES: [u8; 4] = [b'P', b'Q', b'R', b'S'];
+const FUNCTION_NUMBERS: [u16; 8] = [15, 17, 18, 19, 20, 21, 23, 24];
+
+// Letters typed with Ctrl send their index in the alphabet.
+const LETTERS: [VirtualKeyCode; 26] = {
+    use VirtualKeyCode::*;
+    
+    [A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z]
+};
+
+// Largest value 1005 encodes, as a two bytes UTF-8 character
+const MOUSE_UTF8_MAX: usize = 0x7FF;
+
+/// Longest time between the clicks of a double or triple click.
+const MULTI_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Number of lines scrolled by one step of the mouse wheel.
+const WHEEL_SCROLL_LINES: f32 = 3.0;
+
+#[derive(Clone, Debug)]
 pub enum TermikuWindowEvent {
     CharacterInput(char),
-    KeyboardArrow(KeyboardArrow)
+    /// Character typed with Alt, sent after an ESC
+    AltCharacterInput(char),
+    /// String sent by a key binding
+    StringInput(String),
+    /// Contents of the clipboard
+    Paste(String),
+    KeyInput(KeyInput),
+    Mouse(MouseReport),
+    Scroll(ScrollView),
+    /// Contents of a clipboard queried by the terminal with the given uid
+    ClipboardContents(usize, ClipboardTarget, String)
 }
 
+/// Keys which don't type a character, or whose character depends on the modes of the terminal
+/// or on the modifiers
 #[derive(Copy, Clone, Debug)]
-pub enum KeyboardArrow {
+pub enum Key {
     Up,
     Down,
     Right,
     Left,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    /// F1 to F12
+    Function(u8),
+    Escape,
+    Backspace,
+    Tab,
+    Return,
+    /// Keys of the numeric keypad, by the character they type in numeric mode
+    Keypad(char),
+    /// Control byte of a key typed with Ctrl
+    Control(u8),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct KeyInput {
+    pub key: Key,
+    pub modifiers: ModifiersState
+}
+
+impl KeyInput {
+    // Bytes sent to the process for the key, following xterm.
+    pub fn to_control_sequence(self, state: &ScreenState) -> Vec<u8> {
+        let modifiers = self.modifiers;
+        
+        match self.key {
+            Key::Up     => cursor_key_sequence(UP_FINAL_BYTE, modifiers, state.application_cursor),
+            Key::Down   => cursor_key_sequence(DOWN_FINAL_BYTE, modifiers, state.application_cursor),
+            Key::Right  => cursor_key_sequence(RIGHT_FINAL_BYTE, modifiers, state.application_cursor),
+            Key::Left   => cursor_key_sequence(LEFT_FINAL_BYTE, modifiers, state.application_cursor),
+            Key::Home   => cursor_key_sequence(HOME_FINAL_BYTE, modifiers, state.application_cursor),
+            Key::End    => cursor_key_sequence(END_FINAL_BYTE, modifiers, state.application_cursor),
+            
+            Key::Insert     => tilde_key_sequence(INSERT_NUMBER, modifiers),
+            Key::Delete     => tilde_key_sequence(DELETE_NUMBER, modifiers),
+            Key::PageUp     => tilde_key_sequence(PAGE_UP_NUMBER, modifiers),
+            Key::PageDown   => tilde_key_sequence(PAGE_DOWN_NUMBER, modifiers),
+            
+            Key::Function(number @ 1..=4) => {
+                cursor_key_sequence(FUNCTION_FINAL_BYTES[number as usize - 1], modifiers, true)
+            }
+            Key::Function(number) => {
+                tilde_key_sequence(FUNCTION_NUMBERS[number as usize - 5], modifiers)
+            }
+            
+            Key::Escape => alt_prefixed(&[ESCAPE], modifiers),
+            Key::Backspace if modifiers.ctrl => alt_prefixed(&[BACKSPACE], modifiers),
+            Key::Backspace => alt_prefixed(&[DELETE], modifiers),
+            // Shift+Tab is CBT
+            Key::Tab if modifiers.shift => b"\x1B[Z".to_vec(),
+            Key::Tab => alt_prefixed(b"\t", modifiers),
+            // With LNM, Return sends a line feed after its carriage return.
+            Key::Return if state.new_line_mode => alt_prefixed(b"\r\n", modifiers),
+            Key::Return => alt_prefixed(b"\r", modifiers),
+            
+            // Like xterm with NumLock on, its default, the digits always send their character, and
+            // only Enter sends SS3 in application mode.
+            Key::Keypad('\r') if state.application_keypad => vec![ESCAPE, b'O', KEYPAD_ENTER_FINAL_BYTE],
+            Key::Keypad('\r') => KeyInput { key: Key::Return, ..self }.to_control_sequence(state),
+            Key::Keypad(character) => vec![character as u8],
+            
+            Key::Control(byte) => alt_prefixed(&[byte], modifiers),
+        }
+    }
+}
+
+/// Buttons reported to the program. The wheel is reported as buttons which are only pressed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
 }
 
-impl KeyboardArrow {
-    pub fn to_control_sequence(self) -> &'static str {
-        match self {
-            KeyboardArrow::Up       => UP_CONTROL_SEQUENCE,
-            KeyboardArrow::Down     => DOWN_CONTROL_SEQUENCE,
-            KeyboardArrow::Right    => RIGHT_CONTROL_SEQUENCE,
-            KeyboardArrow::Left     => LEFT_CONTROL_SEQUENCE,
+#[derive(Copy, Clone, Debug)]
+pub enum MouseAction {
+    Press(MouseButton),
+    Release(MouseButton),
+    /// Motion to another cell, with the button held if any
+    Motion(Option<MouseButton>),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MouseReport {
+    pub action: MouseAction,
+    /// Cell under the mouse, starting at 1
+    pub column: usize,
+    pub row: usize,
+    pub modifiers: ModifiersState
+}
+
+impl MouseReport {
+    // Bytes sent to the process for the event, following xterm, or None if the encoding can't
+    // represent the position.
+    pub fn to_control_sequence(self, encoding: MouseEncoding) -> Option<Vec<u8>> {
+        let (button, release) = match self.action {
+            MouseAction::Press(button) => (get_button_code(button), false),
+            // Only SGR tells which button was released, the other encodings send 3.
+            MouseAction::Release(button) if encoding == MouseEncoding::Sgr => (get_button_code(button), true),
+            MouseAction::Release(_) => (3, true),
+            // Motion without any button held is reported like a release.
+            MouseAction::Motion(button) => (button.map_or(3, get_button_code) + 32, false),
+        };
+        
+        let modifiers = self.modifiers;
+        let button = button + (modifiers.shift as usize) * 4 + (modifiers.alt as usize) * 8 + (modifiers.ctrl as usize) * 16;
+        let (column, row) = (self.column, self.row);
+        
+        match encoding {
+            MouseEncoding::X10 => {
+                let values = [button + 32, column + 32, row + 32];
+                
+                if values.iter().any(|value| *value > 0xFF) {
+                    return None;
+                }
+                
+                let mut sequence = b"\x1B[M".to_vec();
+                sequence.extend(values.iter().map(|value| *value as u8));
+                Some(sequence)
+            }
+            MouseEncoding::Utf8 => {
+                let mut sequence = String::from("\x1B[M");
+                
+                for value in [button + 32, column + 32, row + 32].iter() {
+                    if *value > MOUSE_UTF8_MAX {
+                        return None;
+                    }
+                    
+                    sequence.push(std::char::from_u32(*value as u32)?);
+                }
+                
+                Some(sequence.into_bytes())
+            }
+            MouseEncoding::Sgr => {
+                let final_byte = if release { 'm' } else { 'M' };
+                
+                Some(format!("\x1B[<{};{};{}{}", button, column, row, final_byte).into_bytes())
+            }
+            MouseEncoding::Urxvt => Some(format!("\x1B[{};{};{}M", button + 32, column, row).into_bytes())
         }
     }
 }
 
+/// Mouse as seen by the window, whose events are turned into reports when the program tracks
+/// them
+#[derive(Default)]
+pub struct MouseState {
+    /// Cell under the mouse, starting at 1
+    column: usize,
+    row: usize,
+    /// Button held, reported with motion
+    button: Option<MouseButton>,
+    /// Time and cell of the last click, and how many clicks quickly followed each other there
+    last_click: Option<(Instant, usize, usize)>,
+    clicks: usize,
+}
+
+impl MouseState {
+    // Positions are in pixels, with the lines drawn from `delta_height`.
+    pub fn moved(&mut self, x: f64, y: f64, cell_size: RectSize, delta_height: u32,
+        tracking: MouseTracking, modifiers: ModifiersState) -> Option<TermikuWindowEvent> {
+        
+        let column = (x.max(0.0) as u32 / cell_size.width.max(1)) as usize + 1;
+        let row = ((y.max(0.0) as u32).saturating_sub(delta_height) / cell_size.height.max(1)) as usize + 1;
+        
+        if column == self.column && row == self.row {
+            return None;
+        }
+        
+        self.column = column;
+        self.row = row;
+        
+        let reported = match tracking {
+            MouseTracking::AnyEvent => true,
+            MouseTracking::ButtonEvent => self.button.is_some(),
+            _ => false
+        };
+        
+        if reported {
+            Some(self.report(MouseAction::Motion(self.button), modifiers))
+        } else {
+            None
+        }
+    }
+    
+    pub fn input(&mut self, state: ElementState, button: WindowMouseButton,
+        tracking: MouseTracking, modifiers: ModifiersState) -> Option<TermikuWindowEvent> {
+        
+        let button = match button {
+            WindowMouseButton::Left => MouseButton::Left,
+            WindowMouseButton::Middle => MouseButton::Middle,
+            WindowMouseButton::Right => MouseButton::Right,
+            WindowMouseButton::Other(_) => return None
+        };
+        
+        let action = if state == ElementState::Pressed {
+            self.button = Some(button);
+            MouseAction::Press(button)
+        } else {
+            self.button = None;
+            MouseAction::Release(button)
+        };
+        
+        if tracking == MouseTracking::None {
+            None
+        } else {
+            Some(self.report(action, modifiers))
+        }
+    }
+    
+    // The wheel scrolls the view, unless the program tracks the mouse, in which case each step
+    // is reported as a press.
+    pub fn wheel(&self, delta: &MouseScrollDelta, cell_height: u32,
+        tracking: MouseTracking, modifiers: ModifiersState) -> Vec<TermikuWindowEvent> {
+        
+        if tracking == MouseTracking::None {
+            return handle_mouse_wheel(delta, cell_height).into_iter().collect();
+        }
+        
+        let steps = match delta {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            MouseScrollDelta::PixelDelta(position) => (position.y / cell_height.max(1) as f64) as f32,
+        };
+        
+        let button = if steps > 0.0 {
+            MouseButton::WheelUp
+        } else {
+            MouseButton::WheelDown
+        };
+        
+        (0..steps.abs().round() as usize)
+            .map(|_| self.report(MouseAction::Press(button), modifiers))
+            .collect()
+    }
+    
+    pub fn get_cell(&self) -> (usize, usize) {
+        (self.column, self.row)
+    }
+    
+    // A click selects characters, a double click words, and a triple click lines.
+    pub fn click(&mut self) -> SelectionKind {
+        let now = Instant::now();
+        
+        let repeated = match self.last_click {
+            Some((time, column, row)) => {
+                now.duration_since(time) < MULTI_CLICK_INTERVAL && column == self.column && row == self.row
+            },
+            None => false
+        };
+        
+        self.clicks = if repeated {
+            self.clicks % 3 + 1
+        } else {
+            1
+        };
+        self.last_click = Some((now, self.column, self.row));
+        
+        match self.clicks {
+            1 => SelectionKind::Simple,
+            2 => SelectionKind::Word,
+            _ => SelectionKind::Line
+        }
+    }
+    
+    fn report(&self, action: MouseAction, modifiers: ModifiersState) -> TermikuWindowEvent {
+        TermikuWindowEvent::Mouse(MouseReport {
+            action,
+            column: self.column,
+            row: self.row,
+            modifiers
+        })
+    }
+}
+
+/// Movement of the scrollback view. Up goes towards the history.
+#[derive(Copy, Clone, Debug)]
+pub enum ScrollView {
+    Up(usize),
+    Down(usize),
+    PageUp,
+    PageDown,
+}
+
 pub fn handle_keyboard_input(input: &KeyboardInput) -> Option<TermikuWindowEvent> {
-    if let Some(key_code) = input.virtual_keycode {
+    if !pressed(input) {
+        return None;
+    }
+    
+    let key_code = input.virtual_keycode?;
+    let modifiers = input.modifiers;
+    
+    let key = {
         use VirtualKeyCode::*;
         
         match key_code {
-            Up => event_if_pressed(TermikuWindowEvent::KeyboardArrow(KeyboardArrow::Up), input),
-            Down => event_if_pressed(TermikuWindowEvent::KeyboardArrow(KeyboardArrow::Down), input),
-            Right => event_if_pressed(TermikuWindowEvent::KeyboardArrow(KeyboardArrow::Right), input),
-            Left => event_if_pressed(TermikuWindowEvent::KeyboardArrow(KeyboardArrow::Left), input),
-            _ => None
+            PageUp if modifiers.shift => return Some(TermikuWindowEvent::Scroll(ScrollView::PageUp)),
+            PageDown if modifiers.shift => return Some(TermikuWindowEvent::Scroll(ScrollView::PageDown)),
+            
+            Up => Key::Up,
+            Down => Key::Down,
+            Right => Key::Right,
+            Left => Key::Left,
+            Home => Key::Home,
+            End => Key::End,
+            Insert => Key::Insert,
+            Delete => Key::Delete,
+            PageUp => Key::PageUp,
+            PageDown => Key::PageDown,
+            
+            F1 => Key::Function(1),
+            F2 => Key::Function(2),
+            F3 => Key::Function(3),
+            F4 => Key::Function(4),
+            F5 => Key::Function(5),
+            F6 => Key::Function(6),
+            F7 => Key::Function(7),
+            F8 => Key::Function(8),
+            F9 => Key::Function(9),
+            F10 => Key::Function(10),
+            F11 => Key::Function(11),
+            F12 => Key::Function(12),
+            
+            Escape => Key::Escape,
+            Back => Key::Backspace,
+            Tab => Key::Tab,
+            Return => Key::Return,
+            
+            Numpad0 => Key::Keypad('0'),
+            Numpad1 => Key::Keypad('1'),
+            Numpad2 => Key::Keypad('2'),
+            Numpad3 => Key::Keypad('3'),
+            Numpad4 => Key::Keypad('4'),
+            Numpad5 => Key::Keypad('5'),
+            Numpad6 => Key::Keypad('6'),
+            Numpad7 => Key::Keypad('7'),
+            Numpad8 => Key::Keypad('8'),
+            Numpad9 => Key::Keypad('9'),
+            NumpadEnter => Key::Keypad('\r'),
+            
+            _ if modifiers.ctrl => Key::Control(get_control_byte(key_code)?),
+            _ => return None
         }
-    } else {
-        None
+    };
+    
+    Some(TermikuWindowEvent::KeyInput(KeyInput {
+        key,
+        modifiers
+    }))
+}
+
+// Action bound to a pressed key in the configuration, if any.
+pub fn find_key_binding<'a>(bindings: &'a [KeyBinding], input: &KeyboardInput) -> Option<&'a KeyAction> {
+    if !pressed(input) {
+        return None;
     }
     
+    // Keys are named in the configuration like their variant.
+    let name = format!("{:?}", input.virtual_keycode?);
+    
+    bindings.iter()
+        .find(|binding| binding.key.eq_ignore_ascii_case(&name) && modifiers_match(&binding.mods, input.modifiers))
+        .map(|binding| &binding.action)
 }
 
-fn event_if_pressed(
-    event: TermikuWindowEvent, input: &KeyboardInput
-) -> Option<TermikuWindowEvent> {
-    if pressed(input) {
-        Some(event)
+// Characters are typed by the platform, which doesn't add the ESC of Alt.
+pub fn handle_received_character(character: char, modifiers: ModifiersState) -> TermikuWindowEvent {
+    if modifiers.alt {
+        TermikuWindowEvent::AltCharacterInput(character)
     } else {
+        TermikuWindowEvent::CharacterInput(character)
+    }
+}
+
+// A pixel delta (touchpads) is converted to lines using the height of a cell.
+pub fn handle_mouse_wheel(delta: &MouseScrollDelta, cell_height: u32) -> Option<TermikuWindowEvent> {
+    let lines = match delta {
+        MouseScrollDelta::LineDelta(_, y) => y * WHEEL_SCROLL_LINES,
+        MouseScrollDelta::PixelDelta(position) => (position.y / cell_height.max(1) as f64) as f32,
+    };
+    
+    let amount = lines.abs().round() as usize;
+    
+    if amount == 0 {
         None
+    } else if lines > 0.0 {
+        Some(TermikuWindowEvent::Scroll(ScrollView::Up(amount)))
+    } else {
+        Some(TermikuWindowEvent::Scroll(ScrollView::Down(amount)))
     }
 }
 
-fn pressed(input: &KeyboardInput) -> bool {
+// Bytes written for pasted text. With bracketed paste, the text is enclosed in ESC[200~ and
+// ESC[201~, and any ESC is dropped from it so that it can't end the paste early.
+pub fn get_paste_sequence(text: &str, bracketed: bool) -> Vec<u8> {
+    if !bracketed {
+        return text.as_bytes().to_vec();
+    }
+    
+    let mut sequence = b"\x1b[200~".to_vec();
+    sequence.extend(text.bytes().filter(|&byte| byte != ESCAPE));
+    sequence.extend(b"\x1b[201~");
+    sequence
+}
+
+pub fn pressed(input: &KeyboardInput) -> bool {
     input.state == ElementState::Pressed
 }
+
+fn modifiers_match(mods: &[KeyModifier], modifiers: ModifiersState) -> bool {
+    mods.contains(&KeyModifier::Shift) == modifiers.shift
+        && mods.contains(&KeyModifier::Ctrl) == modifiers.ctrl
+        && mods.contains(&KeyModifier::Alt) == modifiers.alt
+        && mods.contains(&KeyModifier::Logo) == modifiers.logo
+}
+
+// xterm's modifier parameter: 1, plus 1 for Shift, 2 for Alt and 4 for Ctrl
+fn get_modifier_parameter(modifiers: ModifiersState) -> u16 {
+    1 + modifiers.shift as u16 + (modifiers.alt as u16) * 2 + (modifiers.ctrl as u16) * 4
+}
+
+// CSI F, or SS3 F in application mode, and CSI 1 ; Pm F when modified
+fn cursor_key_sequence(final_byte: u8, modifiers: ModifiersState, application: bool) -> Vec<u8> {
+    match get_modifier_parameter(modifiers) {
+        1 if application => vec![ESCAPE, b'O', final_byte],
+        1 => vec![ESCAPE, b'[', final_byte],
+        parameter => {
+            let mut sequence = format!("\x1B[1;{}", parameter).into_bytes();
+            sequence.push(final_byte);
+            sequence
+        }
+    }
+}
+
+// CSI Pn ~, and CSI Pn ; Pm ~ when modified
+fn tilde_key_sequence(number: u16, modifiers: ModifiersState) -> Vec<u8> {
+    match get_modifier_parameter(modifiers) {
+        1 => format!("\x1B[{}~", number).into_bytes(),
+        parameter => format!("\x1B[{};{}~", number, parameter).into_bytes()
+    }
+}
+
+fn alt_prefixed(bytes: &[u8], modifiers: ModifiersState) -> Vec<u8> {
+    let mut sequence = Vec::with_capacity(bytes.len() + 1);
+    
+    if modifiers.alt {
+        sequence.push(ESCAPE);
+    }
+    
+    sequence.extend(bytes);
+    sequence
+}
+
+fn get_button_code(button: MouseButton) -> usize {
+    match button {
+        MouseButton::Left       => 0,
+        MouseButton::Middle     => 1,
+        MouseButton::Right      => 2,
+        MouseButton::WheelUp    => 64,
+        MouseButton::WheelDown  => 65,
+    }
+}
+
+// Ctrl with a letter, or with the few other keys xterm gives a control byte to
+fn get_control_byte(key_code: VirtualKeyCode) -> Option<u8> {
+    use VirtualKeyCode::*;
+    
+    match key_code {
+        Space => Some(0x00),
+        LBracket => Some(0x1B),
+        Backslash => Some(0x1C),
+        RBracket => Some(0x1D),
+        Slash => Some(0x1F),
+        _ => LETTERS
+            .iter()
+            .position(|letter| *letter == key_code)
+            .map(|index| index as u8 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn key(key: Key, modifiers: ModifiersState, state: &ScreenState) -> Vec<u8> {
+        KeyInput { key, modifiers }.to_control_sequence(state)
+    }
+    
+    #[test]
+    fn modified_cursor_keys_send_the_modifier_parameter() {
+        let state = ScreenState::default();
+        let ctrl = ModifiersState { ctrl: true, ..ModifiersState::default() };
+        let shift = ModifiersState { shift: true, ..ModifiersState::default() };
+        
+        assert_eq!(key(Key::Left, ctrl, &state), b"\x1B[1;5D");
+        assert_eq!(key(Key::Home, shift, &state), b"\x1B[1;2H");
+        assert_eq!(key(Key::Delete, ctrl, &state), b"\x1B[3;5~");
+    }
+    
+    #[test]
+    fn cursor_keys_send_ss3_with_decckm() {
+        let mut state = ScreenState::default();
+        
+        assert_eq!(key(Key::Up, ModifiersState::default(), &state), b"\x1B[A");
+        
+        state.application_cursor = true;
+        
+        assert_eq!(key(Key::Up, ModifiersState::default(), &state), b"\x1BOA");
+        assert_eq!(key(Key::End, ModifiersState::default(), &state), b"\x1BOF");
+    }
+    
+    #[test]
+    fn keypad_digits_send_their_character_in_application_mode() {
+        let state = ScreenState {
+            application_keypad: true,
+            ..ScreenState::default()
+        };
+        
+        assert_eq!(key(Key::Keypad('1'), ModifiersState::default(), &state), b"1");
+        assert_eq!(key(Key::Keypad('\r'), ModifiersState::default(), &state), b"\x1BOM");
+    }
+    
+    fn mouse(action: MouseAction, encoding: MouseEncoding) -> Option<Vec<u8>> {
+        let report = MouseReport { action, column: 5, row: 3, modifiers: ModifiersState::default() };
+        
+        report.to_control_sequence(encoding)
+    }
+    
+    #[test]
+    fn sgr_mouse_reports_tell_releases_apart() {
+        assert_eq!(mouse(MouseAction::Press(MouseButton::Left), MouseEncoding::Sgr), Some(b"\x1B[<0;5;3M".to_vec()));
+        assert_eq!(mouse(MouseAction::Release(MouseButton::Left), MouseEncoding::Sgr), Some(b"\x1B[<0;5;3m".to_vec()));
+        assert_eq!(mouse(MouseAction::Press(MouseButton::WheelUp), MouseEncoding::Sgr), Some(b"\x1B[<64;5;3M".to_vec()));
+    }
+    
+    #[test]
+    fn x10_mouse_reports_offset_their_bytes() {
+        assert_eq!(mouse(MouseAction::Press(MouseButton::Right), MouseEncoding::X10), Some(b"\x1B[M\"%#".to_vec()));
+        assert_eq!(mouse(MouseAction::Release(MouseButton::Right), MouseEncoding::X10), Some(b"\x1B[M#%#".to_vec()));
+    }
+}